@@ -1,8 +1,25 @@
-const COMMANDS: &[&str] = &["get_program_list", "get_program_by_path", "suspend", "resume", "is_active"];
+const COMMANDS: &[&str] = &[
+    "get_program_list",
+    "get_program_by_path",
+    "suspend",
+    "resume",
+    "suspend_for",
+    "suspend_until",
+    "is_active",
+    "get_aggregates",
+    "get_shell_integration",
+    "get_productivity",
+    "explain_classification",
+    "get_presence",
+    "get_status",
+    "get_events_since",
+    "start_manual_activity",
+    "stop_manual_activity",
+];
 
 fn main() {
-  tauri_plugin::Builder::new(COMMANDS)
-    .android_path("android")
-    .ios_path("ios")
-    .build();
+    tauri_plugin::Builder::new(COMMANDS)
+        .android_path("android")
+        .ios_path("ios")
+        .build();
 }
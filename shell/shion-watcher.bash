@@ -0,0 +1,44 @@
+# shion-watcher shell integration for bash, source it from ~/.bashrc.
+# Reports command start/end and the working directory to the watcher.
+
+__shion_watcher_socket="${SHION_WATCHER_SOCKET:-@SOCKET@}"
+__shion_watcher_at_prompt=
+
+__shion_watcher_json() {
+  local s=$1
+  s=${s//\\/\\\\}
+  s=${s//\"/\\\"}
+  s=${s//$'\n'/\\n}
+  s=${s//$'\t'/\\t}
+  printf '"%s"' "$s"
+}
+
+__shion_watcher_send() {
+  [ -S "$__shion_watcher_socket" ] || return
+  if command -v socat >/dev/null 2>&1; then
+    (printf '%s\n' "$1" | socat - "UNIX-CONNECT:$__shion_watcher_socket" >/dev/null 2>&1 &)
+  else
+    (printf '%s\n' "$1" | nc -N -U "$__shion_watcher_socket" >/dev/null 2>&1 &)
+  fi
+}
+
+__shion_watcher_preexec() {
+  [ -n "$COMP_LINE" ] && return
+  [ -n "$__shion_watcher_at_prompt" ] || return
+  case "$BASH_COMMAND" in __shion_watcher_*) return ;; esac
+  __shion_watcher_at_prompt=
+  __shion_watcher_send "{\"type\":\"commandStart\",\"pid\":$$,\"cwd\":$(__shion_watcher_json "$PWD"),\"command\":$(__shion_watcher_json "$BASH_COMMAND")}"
+}
+
+__shion_watcher_precmd() {
+  local code=$?
+  __shion_watcher_at_prompt=
+  __shion_watcher_send "{\"type\":\"commandEnd\",\"pid\":$$,\"cwd\":$(__shion_watcher_json "$PWD"),\"status\":$code}"
+}
+
+__shion_watcher_ready() {
+  __shion_watcher_at_prompt=1
+}
+
+trap '__shion_watcher_preexec' DEBUG
+PROMPT_COMMAND="__shion_watcher_precmd${PROMPT_COMMAND:+;$PROMPT_COMMAND};__shion_watcher_ready"
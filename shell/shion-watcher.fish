@@ -0,0 +1,31 @@
+# shion-watcher shell integration for fish, source it from ~/.config/fish/config.fish.
+# Reports command start/end and the working directory to the watcher.
+
+if set -q SHION_WATCHER_SOCKET
+    set -g __shion_watcher_socket $SHION_WATCHER_SOCKET
+else
+    set -g __shion_watcher_socket '@SOCKET@'
+end
+
+function __shion_watcher_json
+    printf '"%s"' (string replace -a -- '\\' '\\\\' $argv[1] | string replace -a -- '"' '\\"' | string replace -a -- \t '\\t' | string join '\n')
+end
+
+function __shion_watcher_send
+    test -S $__shion_watcher_socket; or return
+    if command -q socat
+        printf '%s\n' $argv[1] | socat - UNIX-CONNECT:$__shion_watcher_socket >/dev/null 2>&1 &
+    else
+        printf '%s\n' $argv[1] | nc -N -U $__shion_watcher_socket >/dev/null 2>&1 &
+    end
+    disown 2>/dev/null
+end
+
+function __shion_watcher_preexec --on-event fish_preexec
+    __shion_watcher_send "{\"type\":\"commandStart\",\"pid\":$fish_pid,\"cwd\":"(__shion_watcher_json $PWD)",\"command\":"(__shion_watcher_json $argv[1])"}"
+end
+
+function __shion_watcher_postexec --on-event fish_postexec
+    set -l code $status
+    __shion_watcher_send "{\"type\":\"commandEnd\",\"pid\":$fish_pid,\"cwd\":"(__shion_watcher_json $PWD)",\"status\":$code}"
+end
@@ -0,0 +1,228 @@
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::watcher;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "state")]
+pub enum BackendState {
+    Running,
+    /// The thread returned, e.g. polling without an OS idle time.
+    Stopped,
+    Failed {
+        error: String,
+    },
+}
+
+/// A thread feeding the watcher, like the window watcher or the input hook.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendStatus {
+    pub name: String,
+    #[serde(flatten)]
+    pub state: BackendState,
+    /// When the state started.
+    pub since: i64,
+    /// Times the thread was restarted after failing.
+    pub restarts: u32,
+}
+
+/// Wait before the first restart of a failed backend, doubled for each failure in a row.
+const RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+/// A backend failing after running this long restarts without waiting longer.
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+pub struct Backends {
+    statuses: Mutex<Vec<BackendStatus>>,
+    backoff: Duration,
+}
+
+impl Default for Backends {
+    fn default() -> Self {
+        Self {
+            statuses: Mutex::default(),
+            backoff: RESTART_BACKOFF,
+        }
+    }
+}
+
+impl Backends {
+    pub fn set(&self, name: &str, state: BackendState) {
+        let mut statuses = self.statuses.lock();
+        let restarts = statuses
+            .iter()
+            .find(|s| s.name == name)
+            .map_or(0, |s| s.restarts);
+        let status = BackendStatus {
+            name: name.to_string(),
+            state,
+            since: Utc::now().timestamp_millis(),
+            restarts,
+        };
+        match statuses.iter_mut().find(|s| s.name == name) {
+            Some(current) => *current = status,
+            None => statuses.push(status),
+        }
+    }
+
+    fn restarted(&self, name: &str) {
+        self.set(name, BackendState::Running);
+        if let Some(status) = self.statuses.lock().iter_mut().find(|s| s.name == name) {
+            status.restarts += 1;
+        }
+    }
+
+    pub fn statuses(&self) -> Vec<BackendStatus> {
+        self.statuses.lock().clone()
+    }
+
+    /// Runs `f`, turning an error or a panic into a failed state.
+    fn run<F>(name: &str, f: F) -> BackendState
+    where
+        F: FnOnce() -> anyhow::Result<()>,
+    {
+        let error = match panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(Ok(())) => return BackendState::Stopped,
+            Ok(Err(err)) => err.to_string(),
+            Err(panic) => panic_message(&*panic),
+        };
+        error!("{} error: {}", name, error);
+        BackendState::Failed { error }
+    }
+
+    /// Runs `f` on its own thread, recording whether it is still running.
+    pub fn spawn<F>(self: &Arc<Self>, name: &str, f: F)
+    where
+        F: FnOnce() -> anyhow::Result<()> + Send + 'static,
+    {
+        self.set(name, BackendState::Running);
+        let backends = Arc::clone(self);
+        let name = name.to_string();
+        thread::spawn(move || {
+            let state = Self::run(&name, f);
+            backends.set(&name, state);
+        });
+    }
+
+    /// Like [`Self::spawn`], but runs `f` again whenever it fails, until shutdown.
+    pub fn supervise<F>(self: &Arc<Self>, name: &str, f: F)
+    where
+        F: Fn() -> anyhow::Result<()> + Send + 'static,
+    {
+        self.set(name, BackendState::Running);
+        let backends = Arc::clone(self);
+        let name = name.to_string();
+        thread::spawn(move || {
+            let mut backoff = backends.backoff;
+            loop {
+                let started = Instant::now();
+                let state = Self::run(&name, &f);
+                let failed = matches!(state, BackendState::Failed { .. });
+                backends.set(&name, state);
+                if !failed {
+                    break;
+                }
+                if started.elapsed() >= STABLE_RUN {
+                    backoff = backends.backoff;
+                }
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_RESTART_BACKOFF);
+                if watcher::is_shutdown() {
+                    break;
+                }
+                info!("restarting {}", name);
+                backends.restarted(&name);
+            }
+        });
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "panicked".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[test]
+    fn test_spawn() {
+        let backends = Arc::new(Backends::default());
+        let (tx, rx) = crossbeam_channel::bounded::<()>(0);
+        backends.spawn("blocked", move || {
+            let _ = rx.recv();
+            Ok(())
+        });
+        backends.spawn("broken", || Err(anyhow!("no display")));
+        thread::sleep(Duration::from_millis(100));
+
+        let state = |name: &str| {
+            backends
+                .statuses()
+                .into_iter()
+                .find(|s| s.name == name)
+                .unwrap()
+                .state
+        };
+        assert_eq!(state("blocked"), BackendState::Running);
+        assert_eq!(
+            state("broken"),
+            BackendState::Failed {
+                error: "no display".into()
+            }
+        );
+        tx.send(()).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(state("blocked"), BackendState::Stopped);
+    }
+
+    #[test]
+    fn test_supervise() {
+        let backends = Arc::new(Backends {
+            backoff: Duration::from_millis(10),
+            ..Default::default()
+        });
+        let runs = Arc::new(AtomicU32::new(0));
+        let (tx, rx) = crossbeam_channel::bounded::<()>(0);
+        backends.supervise("flaky", {
+            let runs = Arc::clone(&runs);
+            move || match runs.fetch_add(1, Ordering::Relaxed) {
+                0 => Err(anyhow!("disconnected")),
+                1 => panic!("lost display"),
+                _ => {
+                    let _ = rx.recv();
+                    Ok(())
+                }
+            }
+        });
+        thread::sleep(Duration::from_millis(200));
+
+        let status = || backends.statuses().into_iter().next().unwrap();
+        assert_eq!(status().state, BackendState::Running);
+        assert_eq!(status().restarts, 2);
+        tx.send(()).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(status().state, BackendState::Stopped);
+        assert_eq!(runs.load(Ordering::Relaxed), 3);
+    }
+}
@@ -0,0 +1,86 @@
+//! Native messaging host for the companion browser extension.
+//!
+//! Reads the active tab sent by the extension (`{"url": ..., "title": ...}`, `url`
+//! is `null` when no tab is focused) and forwards it to the watcher socket.
+//!
+//! A watcher with a custom `socket > path` or `socket > port` is reached with
+//! `--socket=<path>` or `--port=<port>`, or `$SHION_WATCHER_SOCKET` for the path.
+
+use std::io::{self, Read};
+
+use serde::Deserialize;
+use tauri_plugin_shion_watcher::{Client, Message, SocketConfig};
+
+#[derive(Deserialize)]
+struct Tab {
+    url: Option<String>,
+    title: Option<String>,
+}
+
+/// Largest message accepted, a tab is far smaller. Browsers may send up to 4 GiB.
+const MAX_MESSAGE_LEN: usize = 1024 * 1024;
+
+/// Messages are a native-endian `u32` length followed by that many bytes of JSON.
+fn read_message(stdin: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match stdin.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let len = u32::from_ne_bytes(len) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message of {} bytes exceeds {} bytes", len, MAX_MESSAGE_LEN),
+        ));
+    }
+    let mut buf = vec![0u8; len];
+    stdin.read_exact(&mut buf)?;
+    Ok(Some(buf))
+}
+
+/// Connects for each message, the watcher closes idle connections.
+fn send(config: &SocketConfig, message: &Message) {
+    if let Ok(mut client) = Client::connect(config) {
+        let _ = client.send(message);
+    }
+}
+
+/// The socket from `--socket=` and `--port=`, other arguments are passed by the browser.
+fn socket_config(args: impl Iterator<Item = String>) -> SocketConfig {
+    let mut config = SocketConfig::default();
+    for arg in args {
+        if let Some(path) = arg.strip_prefix("--socket=") {
+            config.path = Some(path.into());
+        } else if let Some(port) = arg.strip_prefix("--port=") {
+            if let Ok(port) = port.parse() {
+                config.port = port;
+            }
+        }
+    }
+    config
+}
+
+fn main() {
+    let config = socket_config(std::env::args().skip(1));
+    // the browser starts the host, so its parent is the browser process
+    #[cfg(unix)]
+    let pid = Some(std::os::unix::process::parent_id());
+    #[cfg(not(unix))]
+    let pid = None;
+
+    let mut stdin = io::stdin().lock();
+    while let Ok(Some(buf)) = read_message(&mut stdin) {
+        let tab: Tab = match serde_json::from_slice(&buf) {
+            Ok(tab) => tab,
+            Err(_) => continue,
+        };
+        let message = Message::Tab {
+            pid,
+            url: tab.url,
+            title: tab.title,
+        };
+        send(&config, &message);
+    }
+}
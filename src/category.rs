@@ -0,0 +1,305 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rules::{quote, quote_list, Rule},
+    shared::program_name,
+    Result,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Category {
+    pub name: String,
+    /// Productivity weight, from `-1` (distracting) to `1` (productive).
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CategoryConfig {
+    pub categories: Vec<Category>,
+    /// Tried in order, the first matching rule wins.
+    pub rules: Vec<CategoryRule>,
+    /// Seed program categories from the `Categories=` field of `.desktop` files (Linux).
+    pub desktop_entries: bool,
+}
+
+impl Default for CategoryConfig {
+    fn default() -> Self {
+        let categories = [
+            ("Development", 1.0),
+            ("Office", 1.0),
+            ("Communication", 0.5),
+            ("Education", 0.5),
+            ("Design", 0.5),
+            ("System", 0.0),
+            ("Entertainment", -1.0),
+        ];
+        Self {
+            categories: categories
+                .iter()
+                .map(|(name, weight)| Category {
+                    name: name.to_string(),
+                    weight: *weight,
+                })
+                .collect(),
+            rules: vec![],
+            desktop_entries: true,
+        }
+    }
+}
+
+/// Every non-empty list must match, e.g. `programs` and `domains` for a site in a browser.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryRule {
+    /// Executable names without extension.
+    #[serde(default)]
+    pub programs: Vec<String>,
+    /// Browser domains, also matching their subdomains.
+    #[serde(default)]
+    pub domains: Vec<String>,
+    #[serde(default)]
+    pub projects: Vec<String>,
+    pub category: String,
+}
+
+impl CategoryConfig {
+    /// The category `name` with its weight, `0` for categories not listed.
+    pub fn category(&self, name: &str) -> Category {
+        self.categories
+            .iter()
+            .find(|c| c.name == name)
+            .cloned()
+            .unwrap_or(Category {
+                name: name.to_string(),
+                weight: 0.0,
+            })
+    }
+}
+
+/// Below the user's rules and the project presets.
+const DESKTOP_PRIORITY: i32 = -20;
+
+/// The category config and desktop entries as rules.
+pub fn rules(config: &CategoryConfig) -> Result<Vec<Rule>> {
+    let mut rules = vec![];
+    for (i, rule) in config.rules.iter().enumerate() {
+        let mut line = String::new();
+        for (field, values) in [
+            ("name", &rule.programs),
+            ("domain", &rule.domains),
+            ("project", &rule.projects),
+        ] {
+            if !values.is_empty() {
+                line += &format!("{}={} ", field, quote_list(values));
+            }
+        }
+        line += &format!("=> category={}", quote(&rule.category));
+        rules.extend(Rule::parse(format!("category.rules[{}]", i), &line)?);
+    }
+    if config.desktop_entries {
+        let mut programs: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (program, category) in desktop_categories() {
+            programs.entry(category).or_default().push(program);
+        }
+        for (category, mut programs) in programs {
+            programs.sort();
+            let line = format!(
+                "{} name={} => category={}",
+                DESKTOP_PRIORITY,
+                quote_list(&programs),
+                quote(&category)
+            );
+            rules.extend(Rule::parse("desktop entries", &line)?);
+        }
+    }
+    Ok(rules)
+}
+
+/// Maps freedesktop categories to the default taxonomy.
+fn map_desktop_categories(categories: &[&str]) -> Option<&'static str> {
+    let has = |list: &[&str]| categories.iter().any(|c| list.contains(c));
+    if has(&["WebBrowser"]) {
+        // browsers are categorised by domain
+        None
+    } else if has(&[
+        "Email",
+        "Chat",
+        "InstantMessaging",
+        "IRCClient",
+        "VideoConference",
+        "Telephony",
+    ]) {
+        Some("Communication")
+    } else if has(&["Development", "IDE"]) {
+        Some("Development")
+    } else if has(&["Office"]) {
+        Some("Office")
+    } else if has(&["Game", "AudioVideo", "Audio", "Video"]) {
+        Some("Entertainment")
+    } else if has(&["Graphics"]) {
+        Some("Design")
+    } else if has(&["Education", "Science"]) {
+        Some("Education")
+    } else if has(&["System", "Settings", "Utility"]) {
+        Some("System")
+    } else {
+        None
+    }
+}
+
+/// The program name and category of a `.desktop` file.
+fn parse_desktop_entry(content: &str) -> Option<(String, &'static str)> {
+    let mut in_entry = false;
+    let mut exec = None;
+    let mut categories = None;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_entry {
+            continue;
+        }
+        if let Some(value) = line.strip_prefix("TryExec=") {
+            exec = Some(value);
+        } else if let Some(value) = line.strip_prefix("Exec=") {
+            exec = exec.or(Some(value));
+        } else if let Some(value) = line.strip_prefix("Categories=") {
+            categories = Some(value);
+        }
+    }
+    // skip `env VAR=value` prefixes, `Exec=env LANG=C foo %U`
+    let program = exec?
+        .split_whitespace()
+        .map(|arg| arg.trim_matches('"'))
+        .find(|arg| *arg != "env" && !arg.contains('='))?;
+    let categories: Vec<&str> = categories?.split(';').filter(|c| !c.is_empty()).collect();
+    let category = map_desktop_categories(&categories)?;
+    Some((program_name(program), category))
+}
+
+#[cfg(target_os = "linux")]
+fn desktop_categories() -> HashMap<String, String> {
+    use std::{fs, path::PathBuf};
+
+    let mut dirs = vec![];
+    match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) => dirs.push(PathBuf::from(dir)),
+        None => {
+            if let Some(home) = std::env::var_os("HOME") {
+                dirs.push(PathBuf::from(home).join(".local/share"));
+            }
+        }
+    }
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .unwrap_or_else(|_| "/usr/local/share:/usr/share".to_string());
+    dirs.extend(data_dirs.split(':').map(PathBuf::from));
+    dirs.push(PathBuf::from("/var/lib/flatpak/exports/share"));
+
+    let mut map = HashMap::new();
+    for dir in dirs {
+        let entries = match fs::read_dir(dir.join("applications")) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().map_or(true, |e| e != "desktop") {
+                continue;
+            }
+            let parsed = fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| parse_desktop_entry(&content));
+            // earlier directories take precedence, like the desktop does
+            if let Some((program, category)) = parsed {
+                map.entry(program).or_insert_with(|| category.to_string());
+            }
+        }
+    }
+    map
+}
+
+#[cfg(not(target_os = "linux"))]
+fn desktop_categories() -> HashMap<String, String> {
+    HashMap::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        project::{ProjectConfig, ProjectRuleConfig},
+        rules::{Rules, Subject},
+    };
+
+    #[test]
+    fn test_parse_desktop_entry() {
+        let content = "[Desktop Entry]\nName=Visual Studio Code\nExec=/usr/share/code/code --unity-launch %F\nCategories=TextEditor;Development;IDE;\n\n[Desktop Action new-empty-window]\nExec=/usr/share/code/code --new-window %F\n";
+        assert_eq!(
+            parse_desktop_entry(content),
+            Some(("code".into(), "Development"))
+        );
+        let content = "[Desktop Entry]\nExec=env BAMF_DESKTOP_FILE_HINT=x /snap/bin/slack %U\nCategories=GNOME;GTK;Network;InstantMessaging;\n";
+        assert_eq!(
+            parse_desktop_entry(content),
+            Some(("slack".into(), "Communication"))
+        );
+        let content =
+            "[Desktop Entry]\nExec=firefox %u\nCategories=GNOME;GTK;Network;WebBrowser;\n";
+        assert_eq!(parse_desktop_entry(content), None);
+    }
+
+    #[test]
+    fn test_rules() {
+        let category = CategoryConfig {
+            rules: vec![CategoryRule {
+                programs: vec!["firefox".into(), "chrome".into()],
+                domains: vec![],
+                projects: vec!["my \"side\" project".into()],
+                category: "Side projects".into(),
+            }],
+            desktop_entries: false,
+            ..Default::default()
+        };
+        let project = ProjectConfig {
+            presets: false,
+            rules: vec![ProjectRuleConfig {
+                programs: vec![],
+                pattern: r"^\[(.+)\]".into(),
+            }],
+        };
+        let rules = Rules::new(&Config {
+            project,
+            category: category.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        let classify = |path, title| {
+            rules
+                .classify(&Subject {
+                    path,
+                    title: Some(title),
+                    domain: None,
+                })
+                .category
+        };
+        assert_eq!(
+            classify("/usr/bin/firefox", "[my \"side\" project] issues"),
+            Some("Side projects".into())
+        );
+        assert_eq!(classify("/usr/bin/firefox", "[work] issues"), None);
+        assert_eq!(
+            classify("/usr/bin/code", "[my \"side\" project] issues"),
+            None
+        );
+
+        assert_eq!(category.category("Entertainment").weight, -1.0);
+        assert_eq!(category.category("Side projects").weight, 0.0);
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+static EPOCH: Lazy<Instant> = Lazy::new(Instant::now);
+
+/// Jumps smaller than this are scheduling noise.
+const TOLERANCE: i64 = 10_000;
+
+/// Milliseconds since the plugin started on a clock that is never set, unlike the wall clock.
+pub fn monotonic() -> u64 {
+    EPOCH.elapsed().as_millis() as u64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JumpKind {
+    /// The computer slept or the process was frozen.
+    Sleep,
+    /// The wall clock was set back.
+    Adjusted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Jump {
+    pub kind: JumpKind,
+    /// Wall time on the old clock where sessions end, for sleep the last tick before it.
+    pub from: i64,
+    /// Wall time after the jump.
+    pub to: i64,
+    pub monotonic: u64,
+}
+
+/// Compares wall and monotonic time between regular ticks to notice time the watcher did
+/// not see passing.
+#[derive(Default)]
+pub struct Heartbeat {
+    last: Option<(i64, u64)>,
+}
+
+impl Heartbeat {
+    /// Records a tick at `wall` and `monotonic` milliseconds, returning the jump since the
+    /// last tick if there was one.
+    ///
+    /// The monotonic clock keeps counting during sleep on Windows but stops on Linux, where
+    /// sleeping looks like the wall clock moving forward. Both, and a wall clock set
+    /// forward, count as sleep.
+    pub fn tick(&mut self, wall: i64, monotonic: u64, interval: i64) -> Option<Jump> {
+        let (last_wall, last_monotonic) = self.last.replace((wall, monotonic))?;
+        let elapsed = monotonic.saturating_sub(last_monotonic) as i64;
+        let skew = wall - last_wall - elapsed;
+        let kind = if elapsed > interval + TOLERANCE || skew > TOLERANCE {
+            JumpKind::Sleep
+        } else if skew < -TOLERANCE {
+            JumpKind::Adjusted
+        } else {
+            return None;
+        };
+        let from = match kind {
+            JumpKind::Sleep => last_wall,
+            JumpKind::Adjusted => last_wall + elapsed,
+        };
+        Some(Jump {
+            kind,
+            from,
+            to: wall,
+            monotonic,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat() {
+        let mut heartbeat = Heartbeat::default();
+        assert!(heartbeat.tick(100_000, 0, 1_000).is_none());
+        assert!(heartbeat.tick(101_050, 1_000, 1_000).is_none());
+
+        // monotonic clock counting the sleep
+        let jump = heartbeat.tick(3_700_000, 3_600_000, 1_000).unwrap();
+        assert_eq!(jump.kind, JumpKind::Sleep);
+        assert_eq!(jump.from, 101_050);
+        // monotonic clock stopped
+        let jump = heartbeat.tick(7_300_000, 3_601_000, 1_000).unwrap();
+        assert_eq!(jump.kind, JumpKind::Sleep);
+        assert_eq!(jump.from, 3_700_000);
+
+        let jump = heartbeat.tick(3_700_000, 3_602_000, 1_000).unwrap();
+        assert_eq!(jump.kind, JumpKind::Adjusted);
+        assert_eq!(jump.from, 7_301_000);
+        assert_eq!(jump.to, 3_700_000);
+    }
+}
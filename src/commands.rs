@@ -1,9 +1,21 @@
+use std::time::Duration;
+
 use tauri::{command, AppHandle, Runtime, State, Window};
 
+#[cfg(target_os = "linux")]
+use crate::linux as platform;
 #[cfg(target_os = "windows")]
-use crate::windows;
+use crate::windows as platform;
 
-use crate::{shared::Program, MyState, Result};
+use crate::{
+    journal::Replay,
+    models::WatcherState,
+    presence::PresenceStatus,
+    rules::Explanation,
+    shared::Program,
+    store::{Aggregate, GroupBy, Productivity},
+    MyState, Result,
+};
 
 #[command]
 pub(crate) fn get_program_list<R: Runtime>(
@@ -11,7 +23,10 @@ pub(crate) fn get_program_list<R: Runtime>(
     _window: Window<R>,
     _state: State<'_, MyState<R>>,
 ) -> Result<Vec<Program>> {
-    windows::get_program_list()
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    return platform::get_program_list();
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+    Err(anyhow::anyhow!("program list is not supported on this platform").into())
 }
 
 #[command]
@@ -21,7 +36,10 @@ pub(crate) fn get_program_by_path<R: Runtime>(
     _state: State<'_, MyState<R>>,
     path: String,
 ) -> Result<Program> {
-    windows::get_program_by_path(path)
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    return platform::get_program_by_path(path);
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+    Err(anyhow::anyhow!("{} can not be read on this platform", path).into())
 }
 
 #[command]
@@ -29,8 +47,8 @@ pub(crate) fn suspend<R: Runtime>(
     _app: AppHandle<R>,
     _window: Window<R>,
     state: State<'_, MyState<R>>,
-) {
-    state.watcher.suspend();
+) -> Result<()> {
+    state.watcher.suspend()
 }
 
 #[command]
@@ -38,8 +56,28 @@ pub(crate) fn resume<R: Runtime>(
     _app: AppHandle<R>,
     _window: Window<R>,
     state: State<'_, MyState<R>>,
-) {
-    state.watcher.resume();
+) -> Result<()> {
+    state.watcher.resume()
+}
+
+#[command]
+pub(crate) fn suspend_for<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, MyState<R>>,
+    duration: u64,
+) -> Result<()> {
+    state.watcher.suspend_for(Duration::from_millis(duration))
+}
+
+#[command]
+pub(crate) fn suspend_until<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, MyState<R>>,
+    time: i64,
+) -> Result<()> {
+    state.watcher.suspend_until(time)
 }
 #[command]
 pub(crate) fn is_active<R: Runtime>(
@@ -50,3 +88,92 @@ pub(crate) fn is_active<R: Runtime>(
 ) -> bool {
     state.watcher.is_active(path)
 }
+
+#[command]
+pub(crate) fn get_aggregates<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, MyState<R>>,
+    group_by: GroupBy,
+    since: Option<i64>,
+) -> Vec<Aggregate> {
+    state.watcher.aggregates(group_by, since)
+}
+
+#[command]
+pub(crate) fn get_shell_integration<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, MyState<R>>,
+    shell: String,
+) -> Result<String> {
+    state.watcher.shell_integration(&shell)
+}
+
+#[command]
+pub(crate) fn get_productivity<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, MyState<R>>,
+    since: Option<i64>,
+) -> Productivity {
+    state.watcher.productivity(since)
+}
+
+#[command]
+pub(crate) fn explain_classification<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, MyState<R>>,
+    path: String,
+    title: Option<String>,
+) -> Explanation {
+    state.watcher.explain(path, title)
+}
+
+#[command]
+pub(crate) fn get_presence<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, MyState<R>>,
+) -> PresenceStatus {
+    state.watcher.presence()
+}
+
+#[command]
+pub(crate) fn get_status<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, MyState<R>>,
+) -> WatcherState {
+    state.watcher.state()
+}
+
+#[command]
+pub(crate) fn get_events_since<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, MyState<R>>,
+    seq: u64,
+) -> Replay {
+    state.watcher.events_since(seq)
+}
+
+#[command]
+pub(crate) fn start_manual_activity<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, MyState<R>>,
+    label: String,
+) -> Result<()> {
+    state.watcher.start_manual_activity(label)
+}
+
+#[command]
+pub(crate) fn stop_manual_activity<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, MyState<R>>,
+) -> Result<()> {
+    state.watcher.stop_manual_activity()
+}
@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::{
+    browser::BrowserConfig, category::CategoryConfig, event::InputConfig, git::RepositoryConfig,
+    ignore::IgnoreConfig, ipc::SocketConfig, presence::PresenceConfig, project::ProjectConfig,
+    schedule::ScheduleWindow,
+};
+
+/// Plugin configuration, read from `plugins > shion-watcher` in `tauri.conf.json`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Config {
+    pub project: ProjectConfig,
+    pub repository: RepositoryConfig,
+    /// Local socket for the shell and browser integrations.
+    pub socket: SocketConfig,
+    pub browser: BrowserConfig,
+    pub category: CategoryConfig,
+    pub ignore: IgnoreConfig,
+    pub presence: PresenceConfig,
+    pub input: InputConfig,
+    /// Seconds a program stays active without input, 60 when unset. Rules can set it
+    /// per program.
+    pub idle_timeout: Option<u64>,
+    /// Whether tracking runs on the first start, later starts restore the state saved
+    /// by `suspend` and `resume`.
+    pub start_running: bool,
+    /// Weekly windows in which tracking runs, always when empty.
+    pub schedule: Vec<ScheduleWindow>,
+    /// Rule file, see [`Rule`](crate::rules::Rule) for the syntax. Its rules come before
+    /// those of the `project`, `category` and `ignore` sections at equal priority.
+    pub rules: Option<PathBuf>,
+}
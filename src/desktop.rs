@@ -1,17 +1,23 @@
+use std::{sync::Arc, time::Duration};
+
+use crossbeam_channel::Receiver;
 use serde::de::DeserializeOwned;
 use tauri::{plugin::PluginApi, AppHandle, Runtime};
 
-use crate::models::*;
+use crate::{config::Config, models::*, source::ActivitySource, watcher::Watcher};
 
 pub fn init<R: Runtime, C: DeserializeOwned>(
-    app: &AppHandle<R>,
+    _app: &AppHandle<R>,
     _api: PluginApi<R, C>,
+    watcher: Arc<Watcher<R>>,
 ) -> crate::Result<ShionWatcher<R>> {
-    Ok(ShionWatcher(app.clone()))
+    Ok(ShionWatcher { watcher })
 }
 
 /// Access to the shion-watcher APIs.
-pub struct ShionWatcher<R: Runtime>(AppHandle<R>);
+pub struct ShionWatcher<R: Runtime> {
+    watcher: Arc<Watcher<R>>,
+}
 
 impl<R: Runtime> ShionWatcher<R> {
     pub fn ping(&self, payload: PingRequest) -> crate::Result<PingResponse> {
@@ -19,4 +25,50 @@ impl<R: Runtime> ShionWatcher<R> {
             value: payload.value,
         })
     }
+
+    /// Programs becoming active or inactive, the payloads of the `status-changed` event.
+    /// Events are kept until received, drop the receiver to unsubscribe.
+    pub fn subscribe(&self) -> Receiver<WindowStatus> {
+        self.watcher.subscribe()
+    }
+
+    /// Whether tracking runs, the backends and the active programs.
+    pub fn snapshot(&self) -> WatcherState {
+        self.watcher.state()
+    }
+
+    pub fn suspend(&self) -> crate::Result<()> {
+        self.watcher.suspend()
+    }
+
+    pub fn suspend_for(&self, duration: Duration) -> crate::Result<()> {
+        self.watcher.suspend_for(duration)
+    }
+
+    /// Suspends until `time` in milliseconds.
+    pub fn suspend_until(&self, time: i64) -> crate::Result<()> {
+        self.watcher.suspend_until(time)
+    }
+
+    pub fn resume(&self) -> crate::Result<()> {
+        self.watcher.resume()
+    }
+
+    /// Logs offline work as `label` until [`Self::stop_manual_activity`].
+    pub fn start_manual_activity(&self, label: String) -> crate::Result<()> {
+        self.watcher.start_manual_activity(label)
+    }
+
+    pub fn stop_manual_activity(&self) -> crate::Result<()> {
+        self.watcher.stop_manual_activity()
+    }
+
+    /// Starts a source of activity besides the built-in watchers.
+    pub fn add_source(&self, source: impl ActivitySource) {
+        self.watcher.add_source(Box::new(source));
+    }
+
+    pub fn config(&self) -> &Config {
+        self.watcher.config()
+    }
 }
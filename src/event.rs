@@ -1,38 +1,428 @@
-use rdev::{listen, Event, EventType, ListenError};
+use std::{
+    collections::HashSet,
+    thread,
+    time::{Duration, Instant},
+};
 
+use anyhow::anyhow;
+use rdev::{listen, Event, EventType, Key, ListenError};
+use serde::Deserialize;
+
+#[cfg(target_os = "linux")]
+use crate::linux;
 #[cfg(target_os = "windows")]
 use crate::windows;
 
-use crate::watcher::{WatcherEvent, WATCHER_EVENT_CHANNEL};
+use crate::{
+    presence,
+    shared::WindowInfo,
+    watcher::{self, WatcherEvent, WATCHER_EVENT_CHANNEL, WATCHER_TOGGLE_CHANNEL},
+    Result,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Bursts of input, like typing, resolve the foreground program a few times per second.
+const RESOLVE_INTERVAL: Duration = Duration::from_millis(250);
+
+const MODIFIERS: [[Key; 2]; 4] = [
+    [Key::ControlLeft, Key::ControlRight],
+    [Key::Alt, Key::AltGr],
+    [Key::ShiftLeft, Key::ShiftRight],
+    [Key::MetaLeft, Key::MetaRight],
+];
+const LETTERS: [Key; 26] = [
+    Key::KeyA,
+    Key::KeyB,
+    Key::KeyC,
+    Key::KeyD,
+    Key::KeyE,
+    Key::KeyF,
+    Key::KeyG,
+    Key::KeyH,
+    Key::KeyI,
+    Key::KeyJ,
+    Key::KeyK,
+    Key::KeyL,
+    Key::KeyM,
+    Key::KeyN,
+    Key::KeyO,
+    Key::KeyP,
+    Key::KeyQ,
+    Key::KeyR,
+    Key::KeyS,
+    Key::KeyT,
+    Key::KeyU,
+    Key::KeyV,
+    Key::KeyW,
+    Key::KeyX,
+    Key::KeyY,
+    Key::KeyZ,
+];
+const DIGITS: [Key; 10] = [
+    Key::Num0,
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+];
+const FUNCTION_KEYS: [Key; 12] = [
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+];
 
-pub fn run() -> Result<(), ListenError> {
-    let activate = |path: String| {
-        let _ = WATCHER_EVENT_CHANNEL.lock().0.send(WatcherEvent {
-            path,
-            is_audio: false,
-            active: true,
-        });
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct InputConfig {
+    /// Install a global keyboard and mouse hook. When disabled, input is detected by
+    /// polling the OS idle time instead.
+    pub hook: bool,
+    /// Count mouse movement as input, e.g. while reading.
+    pub mouse_move: bool,
+    /// Pixels the pointer has to travel to count as input, filtering out jitter.
+    pub mouse_move_distance: f64,
+    /// Minimum milliseconds between mouse movements counted as input.
+    pub mouse_move_interval: u64,
+    /// Key combination toggling tracking, e.g. `Ctrl+Alt+P`. Needs the hook.
+    pub hotkey: Option<String>,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            hook: true,
+            mouse_move: true,
+            mouse_move_distance: 20.0,
+            mouse_move_interval: 1000,
+            hotkey: None,
+        }
+    }
+}
+
+/// Turns the stream of pointer positions into occasional input.
+struct MouseFilter {
+    distance: f64,
+    interval: Duration,
+    origin: Option<(f64, f64)>,
+    last: Option<Instant>,
+}
+
+impl MouseFilter {
+    fn new(config: &InputConfig) -> Self {
+        Self {
+            distance: config.mouse_move_distance,
+            interval: Duration::from_millis(config.mouse_move_interval),
+            origin: None,
+            last: None,
+        }
+    }
+
+    /// Whether moving to `x`, `y` counts as input.
+    fn accept(&mut self, x: f64, y: f64, now: Instant) -> bool {
+        let (origin_x, origin_y) = *self.origin.get_or_insert((x, y));
+        if (x - origin_x).hypot(y - origin_y) < self.distance {
+            return false;
+        }
+        if self
+            .last
+            .is_some_and(|last| now.duration_since(last) < self.interval)
+        {
+            return false;
+        }
+        self.origin = Some((x, y));
+        self.last = Some(now);
+        true
+    }
+}
+
+#[derive(Default)]
+struct Throttle {
+    last: Option<Instant>,
+}
+
+impl Throttle {
+    fn ready(&mut self, now: Instant) -> bool {
+        if self
+            .last
+            .is_some_and(|last| now.duration_since(last) < RESOLVE_INTERVAL)
+        {
+            return false;
+        }
+        self.last = Some(now);
+        true
+    }
+}
+
+fn parse_key(name: &str) -> Option<Key> {
+    let name = name.to_lowercase();
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return match c {
+            'a'..='z' => Some(LETTERS[(c as u8 - b'a') as usize]),
+            '0'..='9' => Some(DIGITS[(c as u8 - b'0') as usize]),
+            _ => None,
+        };
+    }
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<usize>().ok()) {
+        return n.checked_sub(1).and_then(|i| FUNCTION_KEYS.get(i)).copied();
+    }
+    let key = match name.as_str() {
+        "space" => Key::Space,
+        "enter" | "return" => Key::Return,
+        "tab" => Key::Tab,
+        "esc" | "escape" => Key::Escape,
+        "backspace" => Key::Backspace,
+        "insert" => Key::Insert,
+        "delete" => Key::Delete,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "pause" => Key::Pause,
+        "up" => Key::UpArrow,
+        "down" => Key::DownArrow,
+        "left" => Key::LeftArrow,
+        "right" => Key::RightArrow,
+        _ => return None,
     };
+    Some(key)
+}
+
+/// A key combination like `Ctrl+Alt+P`, pressed when its key goes down while exactly its
+/// modifiers are held.
+pub struct Hotkey {
+    modifiers: Vec<[Key; 2]>,
+    key: Key,
+    pressed: HashSet<Key>,
+}
+
+impl Hotkey {
+    pub fn parse(hotkey: &str) -> Result<Self> {
+        let invalid = |reason: &str| anyhow!("hotkey `{}`: {}", hotkey, reason);
+        let mut parts: Vec<&str> = hotkey.split('+').map(str::trim).collect();
+        let key = parts.pop().unwrap_or_default();
+        let key = parse_key(key).ok_or_else(|| invalid("unknown key"))?;
+        let mut modifiers = vec![];
+        for part in parts {
+            let index = match part.to_lowercase().as_str() {
+                "ctrl" | "control" => 0,
+                "alt" | "option" => 1,
+                "shift" => 2,
+                "super" | "meta" | "win" | "cmd" => 3,
+                _ => return Err(invalid("unknown modifier").into()),
+            };
+            modifiers.push(MODIFIERS[index]);
+        }
+        if modifiers.is_empty() {
+            return Err(invalid("needs a modifier").into());
+        }
+        Ok(Self {
+            modifiers,
+            key,
+            pressed: HashSet::new(),
+        })
+    }
 
+    /// Whether `event` completes the combination. Held keys repeating do not.
+    fn update(&mut self, event: &EventType) -> bool {
+        match *event {
+            EventType::KeyPress(key) => {
+                if !self.pressed.insert(key) || key != self.key {
+                    return false;
+                }
+                MODIFIERS.iter().all(|modifier| {
+                    let held = modifier.iter().any(|k| self.pressed.contains(k));
+                    held == self.modifiers.contains(modifier)
+                })
+            }
+            EventType::KeyRelease(key) => {
+                self.pressed.remove(&key);
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+fn activate(window: WindowInfo) {
+    let _ = WATCHER_EVENT_CHANNEL.lock().0.send(WatcherEvent {
+        path: window.path,
+        is_audio: false,
+        active: true,
+        input: true,
+        source: None,
+        title: Some(window.title),
+        pid: Some(window.pid),
+    });
+}
+
+fn activate_foreground() {
+    #[cfg(target_os = "windows")]
+    {
+        if let Some(program) = windows::get_foreground_program() {
+            activate(program);
+        }
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(program) = linux::get_foreground_program() {
+            activate(program);
+        }
+    }
+}
+
+fn activate_mouse_area() {
+    #[cfg(target_os = "windows")]
+    {
+        if let Some(program) = windows::get_mouse_area_program() {
+            activate(program);
+        }
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(program) = linux::get_foreground_program() {
+            activate(program);
+        }
+    }
+}
+
+/// Blocks in the hook until the process ends, rdev cannot stop listening. Input after
+/// shutdown is ignored.
+pub fn run(config: &InputConfig) -> std::result::Result<(), ListenError> {
+    let mouse_move = config.mouse_move;
+    let mut filter = MouseFilter::new(config);
+    let mut keyboard = Throttle::default();
+    let mut mouse = Throttle::default();
+    // already validated by the watcher
+    let mut hotkey = config
+        .hotkey
+        .as_deref()
+        .and_then(|hotkey| Hotkey::parse(hotkey).ok());
     listen(move |event: Event| {
+        if watcher::is_shutdown() {
+            return;
+        }
+        let now = Instant::now();
+        if hotkey
+            .as_mut()
+            .is_some_and(|hotkey| hotkey.update(&event.event_type))
+        {
+            let _ = WATCHER_TOGGLE_CHANNEL.lock().0.send(());
+        }
         match event.event_type {
             EventType::KeyPress(_) | EventType::KeyRelease(_) => {
-                #[cfg(target_os = "windows")]
-                {
-                    if let Some(path) = windows::get_foreground_program_path() {
-                        activate(path);
-                    }
+                presence::record_input();
+                if keyboard.ready(now) {
+                    activate_foreground();
                 }
             }
             EventType::ButtonPress(_) | EventType::ButtonRelease(_) | EventType::Wheel { .. } => {
-                #[cfg(target_os = "windows")]
-                {
-                    if let Some(path) = windows::get_mouse_area_program_path() {
-                        activate(path);
-                    }
+                presence::record_input();
+                if mouse.ready(now) {
+                    activate_mouse_area();
+                }
+            }
+            EventType::MouseMove { x, y } if mouse_move && filter.accept(x, y, now) => {
+                presence::record_input();
+                if mouse.ready(now) {
+                    activate_mouse_area();
                 }
             }
             _ => {}
         };
     })
 }
+
+/// Activates the foreground program whenever the OS idle time shows input since the
+/// last poll, for users who do not want a global input hook.
+pub fn poll() {
+    if presence::os_idle_time().is_none() {
+        warn!("no OS idle time available, only window changes are tracked");
+        return;
+    }
+    while !watcher::is_shutdown() {
+        let input =
+            presence::os_idle_time().is_some_and(|idle| idle < POLL_INTERVAL.as_millis() as i64);
+        if input {
+            activate_foreground();
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mouse_filter() {
+        let mut filter = MouseFilter::new(&InputConfig::default());
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        assert!(!filter.accept(100.0, 100.0, at(0)));
+        // jitter
+        assert!(!filter.accept(105.0, 98.0, at(10)));
+        assert!(filter.accept(130.0, 100.0, at(20)));
+        // rate limited, even when far away
+        assert!(!filter.accept(400.0, 400.0, at(500)));
+        assert!(filter.accept(400.0, 400.0, at(1100)));
+        assert!(!filter.accept(410.0, 400.0, at(3000)));
+    }
+
+    #[test]
+    fn test_hotkey() {
+        let mut hotkey = Hotkey::parse("Ctrl+Alt+P").unwrap();
+        let mut press = |events: &[EventType]| events.iter().filter(|e| hotkey.update(e)).count();
+        assert_eq!(
+            press(&[
+                EventType::KeyPress(Key::ControlRight),
+                EventType::KeyPress(Key::Alt),
+                EventType::KeyPress(Key::KeyP),
+                // repeat
+                EventType::KeyPress(Key::KeyP),
+                EventType::KeyRelease(Key::KeyP),
+            ]),
+            1
+        );
+        // another modifier held
+        assert_eq!(
+            press(&[
+                EventType::KeyPress(Key::ShiftLeft),
+                EventType::KeyPress(Key::KeyP),
+                EventType::KeyRelease(Key::KeyP),
+                EventType::KeyRelease(Key::ShiftLeft),
+                EventType::KeyRelease(Key::Alt),
+                EventType::KeyPress(Key::KeyP),
+            ]),
+            0
+        );
+
+        assert!(Hotkey::parse("Super+F12").is_ok());
+        assert!(Hotkey::parse("P").is_err());
+        assert!(Hotkey::parse("Hyper+P").is_err());
+        assert!(Hotkey::parse("Ctrl+F13").is_err());
+    }
+
+    #[test]
+    fn test_throttle() {
+        let mut throttle = Throttle::default();
+        let start = Instant::now();
+        assert!(throttle.ready(start));
+        assert!(!throttle.ready(start + Duration::from_millis(100)));
+        assert!(throttle.ready(start + Duration::from_millis(300)));
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::shared::program_name;
+
+const CACHE_DURATION: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RepositoryConfig {
+    pub enabled: bool,
+    /// Executable names (without extension) whose working directory is inspected.
+    pub programs: Vec<String>,
+}
+
+impl Default for RepositoryConfig {
+    fn default() -> Self {
+        let programs = [
+            // terminals
+            "gnome-terminal-server",
+            "konsole",
+            "alacritty",
+            "kitty",
+            "wezterm-gui",
+            "foot",
+            "xterm",
+            "urxvt",
+            "tilix",
+            "terminator",
+            "xfce4-terminal",
+            "ptyxis",
+            "ghostty",
+            // editors
+            "code",
+            "code-oss",
+            "codium",
+            "zed",
+            "gvim",
+            "emacs",
+            "sublime_text",
+            "idea",
+            "clion",
+            "pycharm",
+            "webstorm",
+            "goland",
+            "rider",
+            "rustrover",
+        ];
+        Self {
+            enabled: true,
+            programs: programs.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Repository {
+    pub root: String,
+    pub name: String,
+    /// `None` for a detached `HEAD`.
+    pub branch: Option<String>,
+}
+
+/// Finds the git repository enclosing `dir`, including worktrees and submodules.
+pub fn find_repository(dir: &Path) -> Option<Repository> {
+    for dir in dir.ancestors() {
+        let dot_git = dir.join(".git");
+        let git_dir = if dot_git.is_dir() {
+            dot_git
+        } else if dot_git.is_file() {
+            let content = fs::read_to_string(&dot_git).ok()?;
+            dir.join(content.strip_prefix("gitdir:")?.trim())
+        } else {
+            continue;
+        };
+        let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+        let branch = head
+            .trim()
+            .strip_prefix("ref: refs/heads/")
+            .map(str::to_string);
+        let root = dir.to_string_lossy().into_owned();
+        let name = dir
+            .file_name()
+            .map_or(root.clone(), |name| name.to_string_lossy().into_owned());
+        return Some(Repository { root, name, branch });
+    }
+    None
+}
+
+/// Resolves the repository a program is working in, cached per process for a few seconds.
+pub struct RepositoryResolver {
+    config: RepositoryConfig,
+    cache: Mutex<HashMap<u32, (Instant, Option<Repository>)>>,
+}
+
+impl RepositoryResolver {
+    pub fn new(config: &RepositoryConfig) -> Self {
+        Self {
+            config: config.clone(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn resolve(&self, pid: u32, path: &str) -> Option<Repository> {
+        let name = program_name(path);
+        if !self.config.enabled || !self.config.programs.contains(&name) {
+            return None;
+        }
+        let mut cache = self.cache.lock();
+        if let Some((time, repository)) = cache.get(&pid) {
+            if time.elapsed() < CACHE_DURATION {
+                return repository.clone();
+            }
+        }
+        let repository = lookup(pid);
+        cache.retain(|_, (time, _)| time.elapsed() < CACHE_DURATION);
+        cache.insert(pid, (Instant::now(), repository.clone()));
+        repository
+    }
+}
+
+/// The newest process of the tree whose working directory is inside a repository,
+/// e.g. the shell (or the editor started from it) of a terminal window.
+#[cfg(target_os = "linux")]
+fn lookup(pid: u32) -> Option<Repository> {
+    use crate::linux::process;
+
+    let mut found: Option<(u64, Repository)> = None;
+    for pid in process::tree(pid) {
+        let repository = process::cwd(pid).and_then(|cwd| find_repository(&cwd));
+        if let Some(repository) = repository {
+            let start_time = process::start_time(pid).unwrap_or(0);
+            if found.as_ref().map_or(true, |(time, _)| start_time >= *time) {
+                found = Some((start_time, repository));
+            }
+        }
+    }
+    found.map(|(_, repository)| repository)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lookup(_pid: u32) -> Option<Repository> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_repository() {
+        let root = std::env::temp_dir().join(format!("shion-watcher-git-{}", std::process::id()));
+        let nested = root.join("src").join("module");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(
+            root.join(".git").join("HEAD"),
+            "ref: refs/heads/feature/x\n",
+        )
+        .unwrap();
+
+        let repository = find_repository(&nested).unwrap();
+        assert_eq!(repository.root, root.to_string_lossy());
+        assert_eq!(repository.branch.as_deref(), Some("feature/x"));
+
+        // worktree: `.git` is a file pointing to the real git dir
+        let worktree = root.join("worktree");
+        fs::create_dir_all(&worktree).unwrap();
+        let git_dir = root.join(".git").join("worktrees").join("wt");
+        fs::create_dir_all(&git_dir).unwrap();
+        fs::write(git_dir.join("HEAD"), "0123456789abcdef\n").unwrap();
+        fs::write(
+            worktree.join(".git"),
+            format!("gitdir: {}\n", git_dir.to_string_lossy()),
+        )
+        .unwrap();
+        let repository = find_repository(&worktree).unwrap();
+        assert_eq!(repository.name, "worktree");
+        assert_eq!(repository.branch, None);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
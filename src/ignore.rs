@@ -0,0 +1,99 @@
+use serde::Deserialize;
+
+use crate::{
+    rules::{quote, quote_list, Rule},
+    Result,
+};
+
+/// Programs that are never tracked.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct IgnoreConfig {
+    /// Ignore the executable of the app using the plugin.
+    pub current_exe: bool,
+    /// Full executable paths.
+    pub paths: Vec<String>,
+    /// Globs matched against the executable path, e.g. `C:\Windows\**`.
+    pub globs: Vec<String>,
+    /// Executable names without extension, e.g. `explorer` or `keepassxc`.
+    pub names: Vec<String>,
+}
+
+impl Default for IgnoreConfig {
+    fn default() -> Self {
+        Self {
+            current_exe: true,
+            paths: vec![],
+            globs: vec![],
+            names: vec![],
+        }
+    }
+}
+
+/// The ignore config as rules.
+pub fn rules(config: &IgnoreConfig) -> Result<Vec<Rule>> {
+    let mut paths = config.paths.clone();
+    if config.current_exe {
+        if let Ok(exe) = std::env::current_exe() {
+            paths.push(exe.to_string_lossy().into_owned());
+        }
+    }
+    let mut lines = vec![];
+    if !paths.is_empty() {
+        lines.push((
+            "ignore.paths",
+            format!("path={} => ignore", quote_list(&paths)),
+        ));
+    }
+    for glob in &config.globs {
+        lines.push(("ignore.globs", format!("path*={} => ignore", quote(glob))));
+    }
+    if !config.names.is_empty() {
+        let names: Vec<String> = config.names.iter().map(|n| n.to_lowercase()).collect();
+        lines.push((
+            "ignore.names",
+            format!("name={} => ignore", quote_list(&names)),
+        ));
+    }
+    let mut rules = vec![];
+    for (source, line) in lines {
+        rules.extend(Rule::parse(source, &line)?);
+    }
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        rules::{Rules, Subject},
+    };
+
+    #[test]
+    fn test_ignore() {
+        let config = Config {
+            ignore: IgnoreConfig {
+                globs: vec!["/usr/lib/1password/**".into()],
+                names: vec!["KeePassXC".into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let rules = Rules::new(&config).unwrap();
+        let ignored = |path: &str| {
+            rules
+                .classify(&Subject {
+                    path,
+                    title: None,
+                    domain: None,
+                })
+                .ignored
+        };
+        let exe = std::env::current_exe().unwrap();
+        assert!(ignored(&exe.to_string_lossy()));
+        assert!(ignored("/usr/lib/1password/1password"));
+        assert!(ignored("/usr/bin/keepassxc"));
+        assert!(!ignored("/usr/bin/code"));
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::watcher::{self, WATCHER_MESSAGE_CHANNEL};
+
+const DEFAULT_PORT: u16 = 47631;
+/// Longest message accepted, messages are a single line of JSON.
+const MAX_LINE_LEN: usize = 64 * 1024;
+/// Connections read at once, further ones are closed right away.
+const MAX_CONNECTIONS: usize = 32;
+/// Idle connections are closed after this long, integrations connect for each message.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the listener checks for new connections and for shutdown.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SocketConfig {
+    pub disabled: bool,
+    /// Unix socket path, defaults to `$SHION_WATCHER_SOCKET`, then `shion-watcher.sock`
+    /// in `$XDG_RUNTIME_DIR`, or else `shion-watcher-<uid>.sock` in the temp dir.
+    pub path: Option<PathBuf>,
+    /// Loopback TCP port, used instead of a unix socket on Windows. Clients authenticate
+    /// with the token next to `path`.
+    pub port: u16,
+}
+
+impl Default for SocketConfig {
+    fn default() -> Self {
+        Self {
+            disabled: false,
+            path: None,
+            port: DEFAULT_PORT,
+        }
+    }
+}
+
+impl SocketConfig {
+    pub fn path(&self) -> PathBuf {
+        if let Some(path) = &self.path {
+            return path.clone();
+        }
+        if let Some(path) = std::env::var_os("SHION_WATCHER_SOCKET") {
+            return PathBuf::from(path);
+        }
+        match std::env::var_os("XDG_RUNTIME_DIR") {
+            Some(dir) => PathBuf::from(dir).join("shion-watcher.sock"),
+            // shared by every user
+            None => std::env::temp_dir().join(temp_socket_name()),
+        }
+    }
+
+    /// Token clients of the TCP port send first, created by the watcher.
+    pub fn token_path(&self) -> PathBuf {
+        self.path().with_extension("token")
+    }
+}
+
+#[cfg(unix)]
+fn temp_socket_name() -> String {
+    // SAFETY: getuid has no preconditions and cannot fail
+    format!("shion-watcher-{}.sock", unsafe { libc::getuid() })
+}
+
+/// The temp dir is per user on Windows.
+#[cfg(not(unix))]
+fn temp_socket_name() -> String {
+    "shion-watcher.sock".to_string()
+}
+
+/// A line of JSON sent to the watcher socket by an integration.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Message {
+    CommandStart {
+        pid: u32,
+        cwd: String,
+        command: String,
+    },
+    CommandEnd {
+        pid: u32,
+        cwd: String,
+        status: Option<i32>,
+    },
+    /// The active tab of a browser, `url` is `None` when no tab is focused.
+    Tab {
+        /// Browser process, if known.
+        pid: Option<u32>,
+        url: Option<String>,
+        title: Option<String>,
+    },
+}
+
+/// A connection to the watcher socket, for integrations written in Rust.
+pub struct Client {
+    stream: Box<dyn Write + Send>,
+}
+
+impl Client {
+    pub fn connect(config: &SocketConfig) -> io::Result<Self> {
+        #[cfg(unix)]
+        let stream = std::os::unix::net::UnixStream::connect(config.path())?;
+        #[cfg(not(unix))]
+        let stream = {
+            let token = std::fs::read_to_string(config.token_path())?;
+            let mut stream = std::net::TcpStream::connect(("127.0.0.1", config.port))?;
+            writeln!(stream, "{}", token.trim())?;
+            stream
+        };
+        Ok(Self {
+            stream: Box::new(stream),
+        })
+    }
+
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+        self.stream.write_all(line.as_bytes())?;
+        self.stream.flush()
+    }
+}
+
+/// Reads a line into `buf` without the newline, `false` at the end of the stream. Lines
+/// longer than `MAX_LINE_LEN` are an error, the connection is not read any further.
+fn read_line(reader: &mut impl BufRead, buf: &mut Vec<u8>) -> io::Result<bool> {
+    buf.clear();
+    let len = reader
+        .take(MAX_LINE_LEN as u64 + 1)
+        .read_until(b'\n', buf)?;
+    if len == 0 {
+        return Ok(false);
+    }
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+    } else if len > MAX_LINE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line exceeds {} bytes", MAX_LINE_LEN),
+        ));
+    }
+    Ok(true)
+}
+
+/// Forwards the messages of a connection, after its first line matched `token` if set.
+fn read<T: io::Read>(stream: T, token: Option<&str>) {
+    let mut reader = BufReader::new(stream);
+    let mut buf = vec![];
+    let mut authenticated = token.is_none();
+    loop {
+        match read_line(&mut reader, &mut buf) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(err) => {
+                warn!("socket read error: {}", err);
+                break;
+            }
+        }
+        if watcher::is_shutdown() {
+            break;
+        }
+        let line = String::from_utf8_lossy(&buf);
+        if !authenticated {
+            if Some(line.trim()) != token {
+                warn!("socket connection with an invalid token");
+                break;
+            }
+            authenticated = true;
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Message>(&line) {
+            Ok(message) => {
+                let _ = WATCHER_MESSAGE_CHANNEL.lock().0.send(message);
+            }
+            Err(err) => warn!("invalid socket message: {}", err),
+        }
+    }
+}
+
+/// Reads `stream` on its own thread, unless `MAX_CONNECTIONS` are already open.
+fn accept<T: io::Read + Send + 'static>(stream: T, token: Option<Arc<str>>) {
+    static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+    if CONNECTIONS.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+        CONNECTIONS.fetch_sub(1, Ordering::SeqCst);
+        warn!(
+            "socket connection refused, {} already open",
+            MAX_CONNECTIONS
+        );
+        return;
+    }
+    thread::spawn(move || {
+        read(stream, token.as_deref());
+        CONNECTIONS.fetch_sub(1, Ordering::SeqCst);
+    });
+}
+
+/// Accepts connections until shutdown, polling the non-blocking listener so it closes once
+/// the app exits.
+fn serve<T>(
+    mut next: impl FnMut() -> io::Result<T>,
+    mut handle: impl FnMut(T) -> io::Result<()>,
+) -> io::Result<()> {
+    while !watcher::is_shutdown() {
+        match next() {
+            Ok(stream) => handle(stream)?,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn run(config: &SocketConfig) -> anyhow::Result<()> {
+    use std::{
+        fs,
+        os::unix::fs::{DirBuilderExt, PermissionsExt},
+        os::unix::net::{UnixListener, UnixStream},
+    };
+
+    let path = config.path();
+    // a socket left behind by a crash is removed, one still answering belongs to a
+    // running instance
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            anyhow::bail!("socket {} is in use by another instance", path.display());
+        }
+        fs::remove_file(&path)?;
+    }
+    // bound inside a private directory and moved into place once only the user can connect
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("not a socket path: {}", path.display()))?;
+    let staging = path.with_file_name(format!(
+        ".{}.{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&staging);
+    fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let bound = staging.join("socket");
+    let listener = UnixListener::bind(&bound).and_then(|listener| {
+        fs::set_permissions(&bound, fs::Permissions::from_mode(0o600))?;
+        fs::rename(&bound, &path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_file(&bound);
+    let _ = fs::remove_dir(&staging);
+    let listener = listener?;
+    listener.set_nonblocking(true)?;
+    info!("socket listening on {}", path.display());
+
+    let result = serve(
+        || listener.accept().map(|(stream, _)| stream),
+        |stream| {
+            stream.set_nonblocking(false)?;
+            stream.set_read_timeout(Some(READ_TIMEOUT))?;
+            accept(stream, None);
+            Ok(())
+        },
+    );
+    let _ = fs::remove_file(&path);
+    Ok(result?)
+}
+
+/// Any local process, or a web page, can reach a loopback port. Connections must start
+/// with the token kept in the user's temp dir.
+#[cfg(not(unix))]
+pub fn run(config: &SocketConfig) -> anyhow::Result<()> {
+    use std::net::TcpListener;
+
+    let token: Arc<str> = load_token(&config.token_path())?.into();
+    let listener = TcpListener::bind(("127.0.0.1", config.port))?;
+    listener.set_nonblocking(true)?;
+    info!("socket listening on 127.0.0.1:{}", config.port);
+
+    serve(
+        || listener.accept().map(|(stream, _)| stream),
+        |stream| {
+            // accepted sockets inherit non-blocking mode on Windows
+            stream.set_nonblocking(false)?;
+            stream.set_read_timeout(Some(READ_TIMEOUT))?;
+            accept(stream, Some(token.clone()));
+            Ok(())
+        },
+    )?;
+    Ok(())
+}
+
+/// The token at `path`, created on first use.
+#[cfg(not(unix))]
+fn load_token(path: &std::path::Path) -> io::Result<String> {
+    use std::fs;
+
+    match fs::read_to_string(path) {
+        Ok(token) if !token.trim().is_empty() => return Ok(token.trim().to_string()),
+        Ok(_) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    fs::write(path, &token)?;
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_line() {
+        let mut buf = vec![];
+        let mut reader = io::Cursor::new(b"{}\nlast".to_vec());
+        assert!(read_line(&mut reader, &mut buf).unwrap());
+        assert_eq!(buf, b"{}");
+        assert!(read_line(&mut reader, &mut buf).unwrap());
+        assert_eq!(buf, b"last");
+        assert!(!read_line(&mut reader, &mut buf).unwrap());
+
+        let mut reader = io::Cursor::new(vec![b'a'; MAX_LINE_LEN + 2]);
+        assert!(read_line(&mut reader, &mut buf).is_err());
+    }
+}
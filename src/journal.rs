@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::models::WatcherState;
+
+/// An emitted event with its payload, including `seq`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventRecord {
+    pub seq: u64,
+    pub event: String,
+    pub payload: Value,
+}
+
+/// What a frontend missed since an event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum Replay {
+    Events {
+        events: Vec<EventRecord>,
+    },
+    /// The events are gone, the state to start over from, up to `seq`.
+    Snapshot {
+        seq: u64,
+        state: WatcherState,
+    },
+}
+
+/// Payloads of emitted events carry a sequence number, increasing by one per event.
+#[derive(Serialize, Clone)]
+pub struct Sequenced<S> {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub payload: S,
+}
+
+/// The latest emitted events.
+pub struct Journal {
+    capacity: usize,
+    seq: u64,
+    records: VecDeque<EventRecord>,
+}
+
+impl Journal {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            seq: 0,
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Sequence number of the latest event, `0` before the first.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn push<S: Serialize + Clone>(
+        &mut self,
+        event: &str,
+        payload: S,
+    ) -> serde_json::Result<Sequenced<S>> {
+        let sequenced = Sequenced {
+            seq: self.seq + 1,
+            payload,
+        };
+        let record = EventRecord {
+            seq: sequenced.seq,
+            event: event.to_string(),
+            payload: serde_json::to_value(&sequenced)?,
+        };
+        self.seq = record.seq;
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+        Ok(sequenced)
+    }
+
+    /// Events after `seq`, `None` when some were dropped or `seq` is from before a restart.
+    pub fn since(&self, seq: u64) -> Option<Vec<EventRecord>> {
+        if seq > self.seq {
+            return None;
+        }
+        let oldest = self.records.front().map_or(self.seq + 1, |r| r.seq);
+        if seq + 1 < oldest {
+            return None;
+        }
+        Some(
+            self.records
+                .iter()
+                .filter(|r| r.seq > seq)
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Clone)]
+    struct Payload {
+        path: &'static str,
+    }
+
+    #[test]
+    fn test_since() {
+        let mut journal = Journal::new(3);
+        assert_eq!(journal.since(0).unwrap().len(), 0);
+        for path in ["a", "b", "c", "d"] {
+            journal.push("status-changed", Payload { path }).unwrap();
+        }
+        assert_eq!(journal.seq(), 4);
+
+        let events = journal.since(2).unwrap();
+        assert_eq!(events.iter().map(|r| r.seq).collect::<Vec<_>>(), vec![3, 4]);
+        assert_eq!(events[0].payload["path"], "c");
+        assert_eq!(events[0].payload["seq"], 3);
+        assert_eq!(journal.since(1).unwrap().len(), 3);
+        assert_eq!(journal.since(4).unwrap().len(), 0);
+        // rolled over
+        assert!(journal.since(0).is_none());
+        // from a previous run
+        assert!(journal.since(9).is_none());
+    }
+}
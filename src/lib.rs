@@ -1,34 +1,72 @@
 use tauri::{
-    plugin::{Builder, TauriPlugin},
-    Manager, Runtime,
+    plugin::{Builder as PluginBuilder, TauriPlugin},
+    Manager, RunEvent, Runtime,
 };
 
 use std::{
     collections::HashMap,
     sync::{Arc, Mutex},
     thread,
+    time::Duration,
 };
 
 #[macro_use]
 extern crate log;
 
+pub use backend::{BackendState, BackendStatus};
+pub use browser::BrowserConfig;
+pub use category::{Category, CategoryConfig, CategoryRule};
+pub use clock::{Jump, JumpKind};
+pub use config::Config;
+pub use event::InputConfig;
+pub use git::{Repository, RepositoryConfig};
+pub use ignore::IgnoreConfig;
+pub use ipc::{Client, Message, SocketConfig};
+pub use journal::{EventRecord, Replay};
 pub use models::*;
+pub use presence::{PresenceConfig, PresenceState, PresenceStatus};
+pub use project::{ProjectConfig, ProjectRuleConfig};
+pub use rules::{Classification, Evaluation, Explanation};
+pub use schedule::{ScheduleCause, ScheduleChange, ScheduleWindow};
+pub use source::{ActivitySender, ActivitySource, SourceEvent};
+pub use store::{
+    Activity, Aggregate, Attribution, GroupBy, MemoryStore, Productivity, Reason, Session, Store,
+};
 
 #[cfg(desktop)]
 mod desktop;
 #[cfg(mobile)]
 mod mobile;
 
+mod backend;
+mod browser;
+mod category;
+mod clock;
 mod commands;
+mod config;
 mod error;
 mod event;
+mod git;
+mod ignore;
+mod ipc;
+mod journal;
 mod models;
+mod persist;
+mod presence;
+mod project;
+mod rules;
+mod schedule;
+mod shared;
+mod shell;
+mod source;
+mod store;
 mod timer;
 mod watcher;
-mod shared;
 
 use watcher::Watcher;
 
+#[cfg(target_os = "linux")]
+mod linux;
 #[cfg(target_os = "windows")]
 mod windows;
 
@@ -54,36 +92,169 @@ impl<R: Runtime, T: Manager<R>> crate::ShionWatcherExt<R> for T {
     }
 }
 
-/// Initializes the plugin.
-pub fn init<R: Runtime>() -> TauriPlugin<R> {
-    Builder::new("shion-watcher")
-        .invoke_handler(tauri::generate_handler![
-            commands::get_program_list,
-            commands::get_program_by_path,
-            commands::suspend,
-            commands::resume,
-            commands::is_active,
-        ])
-        .setup(|app, api| {
-            #[cfg(mobile)]
-            let shion_watcher = mobile::init(app, api)?;
-            #[cfg(desktop)]
-            let shion_watcher = desktop::init(app, api)?;
-            app.manage(shion_watcher);
-
-            let watcher = Watcher::new(app.clone());
-
-            #[cfg(desktop)]
-            thread::spawn({
-                let watcher = watcher.clone();
-                move || {
-                    watcher.run();
+/// How keyboard and mouse input is detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// A global input hook.
+    Hook,
+    /// Polling the OS idle time.
+    Poll,
+}
+
+/// Configures the plugin from Rust. Options set here take precedence over `tauri.conf.json`.
+#[derive(Default)]
+pub struct Builder {
+    config: Option<Config>,
+    idle_timeout: Option<Duration>,
+    backend: Option<Backend>,
+    ignore: Option<IgnoreConfig>,
+    store: Option<Box<dyn Store>>,
+    sources: Vec<Box<dyn ActivitySource>>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the configuration from `tauri.conf.json`.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// How long a program stays active without input, unless a rule sets its timeout.
+    /// Rounded up to whole seconds, at least one.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    pub fn ignore(mut self, ignore: IgnoreConfig) -> Self {
+        self.ignore = Some(ignore);
+        self
+    }
+
+    /// Where finished sessions are kept, in memory by default.
+    pub fn store(mut self, store: impl Store + 'static) -> Self {
+        self.store = Some(Box::new(store));
+        self
+    }
+
+    /// Adds a source of activity besides the built-in watchers.
+    pub fn source(mut self, source: impl ActivitySource) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// The configuration set here on top of `file`, the one from `tauri.conf.json`.
+    fn resolve_config(&mut self, file: Option<Config>) -> Config {
+        let mut config = self.config.take().or(file).unwrap_or_default();
+        if let Some(timeout) = self.idle_timeout {
+            let secs = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+            config.idle_timeout = Some(secs.max(1));
+        }
+        if let Some(backend) = self.backend {
+            config.input.hook = backend == Backend::Hook;
+        }
+        if let Some(ignore) = self.ignore.take() {
+            config.ignore = ignore;
+        }
+        config
+    }
+
+    pub fn build<R: Runtime>(mut self) -> TauriPlugin<R, Option<Config>> {
+        PluginBuilder::<R, Option<Config>>::new("shion-watcher")
+            .invoke_handler(tauri::generate_handler![
+                commands::get_program_list,
+                commands::get_program_by_path,
+                commands::suspend,
+                commands::resume,
+                commands::suspend_for,
+                commands::suspend_until,
+                commands::is_active,
+                commands::get_aggregates,
+                commands::get_shell_integration,
+                commands::get_productivity,
+                commands::explain_classification,
+                commands::get_presence,
+                commands::get_status,
+                commands::get_events_since,
+                commands::start_manual_activity,
+                commands::stop_manual_activity,
+            ])
+            .setup(move |app, api| {
+                let config = self.resolve_config(api.config().clone());
+                let store = self
+                    .store
+                    .unwrap_or_else(|| Box::new(MemoryStore::default()));
+                let watcher = Watcher::new(app.clone(), &config, store)?;
+                for source in self.sources {
+                    watcher.add_source(source);
+                }
+
+                #[cfg(mobile)]
+                let shion_watcher = mobile::init(app, api)?;
+                #[cfg(desktop)]
+                let shion_watcher = desktop::init(app, api, watcher.clone())?;
+                app.manage(shion_watcher);
+
+                #[cfg(desktop)]
+                thread::spawn({
+                    let watcher = watcher.clone();
+                    move || {
+                        watcher.run();
+                    }
+                });
+
+                // manage state so it is accessible by the commands
+                app.manage(MyState { watcher });
+                Ok(())
+            })
+            .on_event(|app, event| {
+                if let RunEvent::Exit = event {
+                    if let Some(state) = app.try_state::<MyState<R>>() {
+                        state.watcher.shutdown();
+                    }
                 }
-            });
+            })
+            .build()
+    }
+}
+
+/// Initializes the plugin.
+pub fn init<R: Runtime>() -> TauriPlugin<R, Option<Config>> {
+    Builder::new().build()
+}
 
-            // manage state so it is accessible by the commands
-            app.manage(MyState { watcher });
-            Ok(())
-        })
-        .build()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_config() {
+        let file = Config {
+            idle_timeout: Some(300),
+            ..Default::default()
+        };
+        let config = Builder::new()
+            .idle_timeout(Duration::from_millis(1500))
+            .backend(Backend::Poll)
+            .resolve_config(Some(file.clone()));
+        assert_eq!(config.idle_timeout, Some(2));
+        assert!(!config.input.hook);
+
+        let config = Builder::new()
+            .idle_timeout(Duration::from_millis(200))
+            .resolve_config(Some(file.clone()));
+        assert_eq!(config.idle_timeout, Some(1));
+
+        let config = Builder::new().resolve_config(Some(file));
+        assert_eq!(config.idle_timeout, Some(300));
+    }
 }
@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use zbus::{
+    blocking::{Connection, MessageIterator, Proxy, ProxyBuilder},
+    zvariant::{OwnedObjectPath, OwnedValue},
+    CacheProperties, MatchRule, Message,
+};
+
+use crate::watcher::{self, SessionEvent, WATCHER_SESSION_CHANNEL};
+
+const DESTINATION: &str = "org.freedesktop.login1";
+const MANAGER_PATH: &str = "/org/freedesktop/login1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+/// The session of the calling process, only for calls: signals use the real path.
+const AUTO_SESSION_PATH: &str = "/org/freedesktop/login1/session/auto";
+const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+static SESSION: Lazy<Option<Proxy<'static>>> = Lazy::new(|| {
+    let session = Connection::system().and_then(|conn| {
+        let path = session_path(&conn);
+        session_proxy(&conn, &path)
+    });
+    match session {
+        Ok(session) => Some(session),
+        Err(err) => {
+            error!("logind connect error: {}", err);
+            None
+        }
+    }
+});
+
+/// The connection `run` listens on, closed by `stop`.
+static LISTENING: Mutex<Option<Connection>> = Mutex::new(None);
+
+/// The object path of the session of the calling process.
+fn session_path(conn: &Connection) -> String {
+    let path = Proxy::new(conn, DESTINATION, MANAGER_PATH, MANAGER_INTERFACE).and_then(|manager| {
+        manager.call::<_, _, OwnedObjectPath>("GetSessionByPID", &(std::process::id()))
+    });
+    match path {
+        Ok(path) => path.as_str().to_string(),
+        Err(_) => AUTO_SESSION_PATH.to_string(),
+    }
+}
+
+fn session_proxy(conn: &Connection, path: &str) -> zbus::Result<Proxy<'static>> {
+    // properties are read on demand, the cache would need signals on the real path
+    ProxyBuilder::new(conn)
+        .destination(DESTINATION)?
+        .path(path.to_string())?
+        .interface(SESSION_INTERFACE)?
+        .cache_properties(CacheProperties::No)
+        .build()
+}
+
+/// Milliseconds since the session went idle, `0` until the desktop marks it idle.
+pub fn idle_time() -> Option<i64> {
+    let session = SESSION.as_ref()?;
+    let idle: bool = session.get_property("IdleHint").ok()?;
+    if !idle {
+        return Some(0);
+    }
+    // microseconds, CLOCK_REALTIME
+    let since: u64 = session.get_property("IdleSinceHint").ok()?;
+    Some((Utc::now().timestamp_millis() - (since / 1000) as i64).max(0))
+}
+
+/// Signals of logind, subscribed before anything is emitted.
+fn subscribe(conn: &Connection) -> zbus::Result<MessageIterator> {
+    // no sender in the rule, peer-to-peer connections have none
+    let rule = MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .path_namespace(MANAGER_PATH)?
+        .build();
+    MessageIterator::for_match_rule(rule, conn, None)
+}
+
+/// The event a logind signal means for the session at `session`.
+fn session_event(
+    conn: &Connection,
+    session: &str,
+    message: &Message,
+) -> zbus::Result<Option<SessionEvent>> {
+    let header = message.header();
+    let (path, interface, member) = match (header.path(), header.interface(), header.member()) {
+        (Some(path), Some(interface), Some(member)) => (path, interface, member),
+        _ => return Ok(None),
+    };
+    let own = path.as_str() == session;
+    let event = match (interface.as_str(), member.as_str()) {
+        (SESSION_INTERFACE, "Lock") if own => Some(SessionEvent::Lock),
+        (SESSION_INTERFACE, "Unlock") if own => Some(SessionEvent::Unlock),
+        (PROPERTIES_INTERFACE, "PropertiesChanged") if own => {
+            let (_, changed, _): (String, HashMap<String, OwnedValue>, Vec<String>) =
+                message.body().deserialize()?;
+            let property = |name: &str| changed.get(name).and_then(|v| bool::try_from(v).ok());
+            match (property("LockedHint"), property("Active")) {
+                (Some(true), _) | (_, Some(false)) => Some(SessionEvent::Lock),
+                (Some(false), _) => Some(SessionEvent::Unlock),
+                // switched back to a session that may still be locked
+                (None, Some(true)) => {
+                    let locked = session_proxy(conn, session)
+                        .and_then(|proxy| proxy.get_property::<bool>("LockedHint"));
+                    match locked {
+                        Ok(true) => None,
+                        _ => Some(SessionEvent::Unlock),
+                    }
+                }
+                (None, None) => None,
+            }
+        }
+        (MANAGER_INTERFACE, "PrepareForSleep") => {
+            let sleeping: bool = message.body().deserialize()?;
+            Some(if sleeping {
+                SessionEvent::Sleep
+            } else {
+                SessionEvent::Wake
+            })
+        }
+        _ => None,
+    };
+    Ok(event)
+}
+
+fn listen(
+    conn: &Connection,
+    messages: MessageIterator,
+    session: &str,
+    mut send: impl FnMut(SessionEvent),
+) -> zbus::Result<()> {
+    for message in messages {
+        if let Some(event) = session_event(conn, session, &message?)? {
+            send(event);
+        }
+    }
+    Ok(())
+}
+
+/// Sends lock, session switch and sleep events of the session of the calling process.
+pub fn run() -> anyhow::Result<()> {
+    let conn = Connection::system()?;
+    let messages = subscribe(&conn)?;
+    let session = session_path(&conn);
+    info!("logind session: {}", session);
+    *LISTENING.lock() = Some(conn.clone());
+    if watcher::is_shutdown() {
+        stop();
+    }
+    let result = listen(&conn, messages, &session, |event| {
+        let _ = WATCHER_SESSION_CHANNEL.lock().0.send(event);
+    });
+    LISTENING.lock().take();
+    // reading fails once `stop` closed the connection
+    if watcher::is_shutdown() {
+        return Ok(());
+    }
+    result?;
+    Ok(())
+}
+
+/// Closes the connection `run` listens on, so it returns.
+pub fn stop() {
+    if let Some(conn) = LISTENING.lock().take() {
+        if let Err(err) = conn.close() {
+            error!("logind close error: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::net::UnixStream, sync::mpsc, thread, time::Duration};
+
+    use zbus::{blocking::ConnectionBuilder, names::BusName, zvariant::Value, Guid};
+
+    use super::*;
+
+    const SESSION: &str = "/org/freedesktop/login1/session/_32";
+
+    /// A private peer-to-peer connection standing in for the system bus, server and client.
+    fn connect() -> (Connection, Connection) {
+        let (server, client) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            ConnectionBuilder::unix_stream(server)
+                .server(Guid::generate())
+                .unwrap()
+                .p2p()
+                .build()
+                .unwrap()
+        });
+        let client = ConnectionBuilder::unix_stream(client)
+            .p2p()
+            .build()
+            .unwrap();
+        (server.join().unwrap(), client)
+    }
+
+    #[test]
+    fn test_stop() {
+        let (_server, client) = connect();
+        let messages = subscribe(&client).unwrap();
+        *LISTENING.lock() = Some(client.clone());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = listen(&client, messages, SESSION, |_| {});
+            let _ = tx.send(());
+        });
+        stop();
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+
+    #[test]
+    fn test_listen() {
+        let (server, client) = connect();
+
+        let messages = subscribe(&client).unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = listen(&client, messages, SESSION, |event| {
+                let _ = tx.send(event);
+            });
+        });
+
+        let emit = |path: &str, interface: &str, member: &str| {
+            server
+                .emit_signal(None::<BusName>, path, interface, member, &())
+                .unwrap();
+        };
+        emit(SESSION, SESSION_INTERFACE, "Lock");
+        // another user's session
+        emit(
+            "/org/freedesktop/login1/session/_41",
+            SESSION_INTERFACE,
+            "Unlock",
+        );
+        server
+            .emit_signal(
+                None::<BusName>,
+                MANAGER_PATH,
+                MANAGER_INTERFACE,
+                "PrepareForSleep",
+                &(true),
+            )
+            .unwrap();
+        let changed = HashMap::from([("LockedHint", Value::from(false))]);
+        server
+            .emit_signal(
+                None::<BusName>,
+                SESSION,
+                PROPERTIES_INTERFACE,
+                "PropertiesChanged",
+                &(SESSION_INTERFACE, changed, Vec::<String>::new()),
+            )
+            .unwrap();
+
+        let timeout = Duration::from_secs(5);
+        let events: Vec<SessionEvent> = (0..3).map(|_| rx.recv_timeout(timeout).unwrap()).collect();
+        assert_eq!(
+            events,
+            [
+                SessionEvent::Lock,
+                SessionEvent::Sleep,
+                SessionEvent::Unlock
+            ]
+        );
+    }
+}
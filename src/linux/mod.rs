@@ -0,0 +1,239 @@
+use std::{path::Path, thread, time::Duration};
+
+use anyhow::anyhow;
+
+use once_cell::sync::Lazy;
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        screensaver::ConnectionExt as _,
+        xproto::{AtomEnum, ChangeWindowAttributesAux, ConnectionExt, EventMask, Window},
+        Event,
+    },
+    rust_connection::RustConnection,
+};
+
+use crate::{
+    shared::{PathCache, Program, WindowInfo},
+    watcher::{self, WatcherEvent, WATCHER_EVENT_CHANNEL},
+    Result,
+};
+
+pub mod logind;
+pub mod process;
+
+static DISPLAY: Lazy<Option<Display>> = Lazy::new(|| match Display::connect() {
+    Ok(display) => Some(display),
+    Err(err) => {
+        error!("x11 connect error: {}", err);
+        None
+    }
+});
+
+static PATHS: Lazy<PathCache> = Lazy::new(PathCache::default);
+
+struct Atoms {
+    net_active_window: u32,
+    net_client_list: u32,
+    net_wm_pid: u32,
+    net_wm_name: u32,
+    utf8_string: u32,
+}
+
+struct Display {
+    conn: RustConnection,
+    root: Window,
+    atoms: Atoms,
+}
+
+impl Display {
+    fn connect() -> anyhow::Result<Self> {
+        let (conn, screen) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen].root;
+        let intern = |name: &[u8]| -> anyhow::Result<u32> {
+            Ok(conn.intern_atom(false, name)?.reply()?.atom)
+        };
+        let atoms = Atoms {
+            net_active_window: intern(b"_NET_ACTIVE_WINDOW")?,
+            net_client_list: intern(b"_NET_CLIENT_LIST")?,
+            net_wm_pid: intern(b"_NET_WM_PID")?,
+            net_wm_name: intern(b"_NET_WM_NAME")?,
+            utf8_string: intern(b"UTF8_STRING")?,
+        };
+        Ok(Self { conn, root, atoms })
+    }
+
+    fn get_u32(&self, window: Window, property: u32, kind: AtomEnum) -> Option<u32> {
+        let reply = self
+            .conn
+            .get_property(false, window, property, kind, 0, 1)
+            .ok()?
+            .reply()
+            .ok()?;
+        let mut value = reply.value32()?;
+        value.next()
+    }
+
+    fn get_title(&self, window: Window) -> String {
+        let properties = [
+            (self.atoms.net_wm_name, self.atoms.utf8_string),
+            (AtomEnum::WM_NAME.into(), AtomEnum::STRING.into()),
+        ];
+        for (property, kind) in properties {
+            let reply = self
+                .conn
+                .get_property(false, window, property, kind, 0, u32::MAX)
+                .ok()
+                .and_then(|cookie| cookie.reply().ok());
+            if let Some(reply) = reply {
+                if !reply.value.is_empty() {
+                    return String::from_utf8_lossy(&reply.value).into_owned();
+                }
+            }
+        }
+        String::new()
+    }
+
+    fn active_window(&self) -> Option<Window> {
+        self.get_u32(self.root, self.atoms.net_active_window, AtomEnum::WINDOW)
+            .filter(|window| *window != 0)
+    }
+
+    /// Top-level windows managed by the window manager.
+    fn client_windows(&self) -> Vec<Window> {
+        let reply = self
+            .conn
+            .get_property(
+                false,
+                self.root,
+                self.atoms.net_client_list,
+                AtomEnum::WINDOW,
+                0,
+                u32::MAX,
+            )
+            .ok()
+            .and_then(|cookie| cookie.reply().ok());
+        match reply.as_ref().and_then(|reply| reply.value32()) {
+            Some(windows) => windows.collect(),
+            None => vec![],
+        }
+    }
+
+    /// Milliseconds since the last input, if the X server supports the screensaver extension.
+    fn idle_time(&self) -> Option<i64> {
+        let reply = self
+            .conn
+            .screensaver_query_info(self.root)
+            .ok()?
+            .reply()
+            .ok()?;
+        Some(reply.ms_since_user_input as i64)
+    }
+
+    fn window_info(&self, window: Window) -> Option<WindowInfo> {
+        let pid = self.get_u32(window, self.atoms.net_wm_pid, AtomEnum::CARDINAL)?;
+        let start_time = process::start_time(pid)?;
+        let path = PATHS.resolve(pid, start_time, || process::exe(pid))?;
+        Some(WindowInfo {
+            pid,
+            path,
+            title: self.get_title(window),
+        })
+    }
+}
+
+/// Programs owning a top-level window.
+pub fn get_program_list() -> Result<Vec<Program>> {
+    let display = match DISPLAY.as_ref() {
+        Some(display) => display,
+        None => return Ok(vec![]),
+    };
+    let mut programs: Vec<Program> = vec![];
+    for window in display.client_windows() {
+        let path = match display
+            .get_u32(window, display.atoms.net_wm_pid, AtomEnum::CARDINAL)
+            .and_then(process::exe)
+        {
+            Some(path) => path,
+            None => continue,
+        };
+        if !programs.iter().any(|p| p.path == path) {
+            programs.push(get_program_by_path(path)?);
+        }
+    }
+    Ok(programs)
+}
+
+/// Executables carry no display name or icon on Linux, so the file name stands in for
+/// the name and the icon is left empty.
+pub fn get_program_by_path(path: String) -> Result<Program> {
+    let name = Path::new(&path)
+        .file_name()
+        .ok_or_else(|| anyhow!("not a program path: {}", path))?
+        .to_string_lossy()
+        .into_owned();
+    Ok(Program {
+        path,
+        name,
+        icon: vec![],
+    })
+}
+
+pub fn get_foreground_program() -> Option<WindowInfo> {
+    let display = DISPLAY.as_ref()?;
+    display.window_info(display.active_window()?)
+}
+
+/// Milliseconds since the last input from the X server, or else from logind, which only
+/// tells once the desktop marks the session idle (e.g. on Wayland).
+pub fn get_idle_time() -> Option<i64> {
+    DISPLAY
+        .as_ref()
+        .and_then(Display::idle_time)
+        .or_else(logind::idle_time)
+}
+
+pub struct App;
+
+impl App {
+    /// Sends an event whenever the window manager reports a new active window, until shutdown.
+    pub fn start() -> anyhow::Result<()> {
+        let display = Display::connect()?;
+        display.conn.change_window_attributes(
+            display.root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+        display.conn.flush()?;
+        info!("watcher start");
+
+        while !watcher::is_shutdown() {
+            let event = match display.conn.poll_for_event()? {
+                Some(event) => event,
+                None => {
+                    thread::sleep(Duration::from_millis(1000 / 60));
+                    continue;
+                }
+            };
+            if let Event::PropertyNotify(event) = event {
+                if event.atom != display.atoms.net_active_window {
+                    continue;
+                }
+                let window = display
+                    .active_window()
+                    .and_then(|window| display.window_info(window));
+                if let Some(window) = window {
+                    let _ = WATCHER_EVENT_CHANNEL.lock().0.send(WatcherEvent {
+                        path: window.path,
+                        is_audio: false,
+                        active: true,
+                        input: false,
+                        source: None,
+                        title: Some(window.title),
+                        pid: Some(window.pid),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use std::{fs, path::PathBuf};
+
+pub fn exe(pid: u32) -> Option<String> {
+    let path = fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+    Some(path.to_string_lossy().into_owned())
+}
+
+pub fn cwd(pid: u32) -> Option<PathBuf> {
+    fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+}
+
+/// Fields of `/proc/<pid>/stat` after the command name, starting at `state`.
+fn stat(pid: u32) -> Option<Vec<String>> {
+    let content = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // the command name may contain spaces and parentheses
+    let rest = &content[content.rfind(')')? + 1..];
+    Some(rest.split_whitespace().map(str::to_string).collect())
+}
+
+pub fn parent(pid: u32) -> Option<u32> {
+    stat(pid)?.get(1)?.parse().ok()
+}
+
+/// Start time in clock ticks after boot.
+pub fn start_time(pid: u32) -> Option<u64> {
+    stat(pid)?.get(19)?.parse().ok()
+}
+
+pub fn children(pid: u32) -> Vec<u32> {
+    let mut list = vec![];
+    let tasks = match fs::read_dir(format!("/proc/{}/task", pid)) {
+        Ok(tasks) => tasks,
+        Err(_) => return list,
+    };
+    for task in tasks.flatten() {
+        if let Ok(content) = fs::read_to_string(task.path().join("children")) {
+            list.extend(
+                content
+                    .split_whitespace()
+                    .filter_map(|p| p.parse::<u32>().ok()),
+            );
+        }
+    }
+    list
+}
+
+/// The process followed by all of its descendants, breadth first.
+pub fn tree(pid: u32) -> Vec<u32> {
+    let mut list = vec![pid];
+    let mut index = 0;
+    while index < list.len() {
+        for child in children(list[index]) {
+            if !list.contains(&child) {
+                list.push(child);
+            }
+        }
+        index += 1;
+    }
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_process() {
+        let pid = std::process::id();
+        assert!(exe(pid).is_some());
+        assert_eq!(cwd(pid), std::env::current_dir().ok());
+        assert_eq!(parent(pid), Some(std::os::unix::process::parent_id()));
+        assert!(start_time(pid).is_some());
+        assert_eq!(tree(pid)[0], pid);
+    }
+}
@@ -1,5 +1,10 @@
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    backend::BackendStatus,
+    store::{Attribution, Reason},
+};
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PingRequest {
@@ -11,3 +16,157 @@ pub struct PingRequest {
 pub struct PingResponse {
     pub value: Option<String>,
 }
+
+/// What last kept a program active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Activation {
+    /// Its window came to the foreground.
+    Focus,
+    /// Keyboard or mouse input in its window.
+    Input,
+    Audio,
+    /// Offline work logged from the frontend.
+    Manual,
+}
+
+/// Version of [`WindowStatus`], increased when its fields change incompatibly.
+pub const WINDOW_STATUS_VERSION: u32 = 2;
+
+/// Why a program became active or inactive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusReason {
+    Focus,
+    Input,
+    AudioStart,
+    AudioStop,
+    /// No input for the program's timeout.
+    Timeout,
+    /// Another program, or another project in the same program, became active.
+    Replaced,
+    Suspended,
+    /// The window reported it is no longer active.
+    Inactive,
+    Locked,
+    Sleep,
+    ClockChanged,
+    /// A manual activity was started.
+    ManualStart,
+    ManualStop,
+    /// The app exited.
+    Shutdown,
+}
+
+impl StatusReason {
+    /// The reason for a session ending with `reason`, of a program playing audio if `is_audio`.
+    pub(crate) fn ended(reason: Reason, is_audio: bool) -> Self {
+        match reason {
+            Reason::Switched => Self::Replaced,
+            Reason::Inactive if is_audio => Self::AudioStop,
+            Reason::Inactive => Self::Inactive,
+            Reason::Timeout => Self::Timeout,
+            Reason::Suspended => Self::Suspended,
+            Reason::Locked => Self::Locked,
+            Reason::Sleep => Self::Sleep,
+            Reason::ClockChanged => Self::ClockChanged,
+            Reason::Stopped => Self::ManualStop,
+            Reason::Shutdown => Self::Shutdown,
+        }
+    }
+}
+
+impl From<Activation> for StatusReason {
+    fn from(activation: Activation) -> Self {
+        match activation {
+            Activation::Focus => Self::Focus,
+            Activation::Input => Self::Input,
+            Activation::Audio => Self::AudioStart,
+            Activation::Manual => Self::ManualStart,
+        }
+    }
+}
+
+/// Payload of the `status-changed` event, a program becoming active or inactive.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowStatus {
+    /// [`WINDOW_STATUS_VERSION`] of the payload.
+    pub version: u32,
+    pub path: String,
+    pub active: bool,
+    pub time: i64,
+    pub reason: StatusReason,
+    pub is_audio: bool,
+    pub pid: Option<u32>,
+    pub active_since: i64,
+    /// Milliseconds the program was active, when it becomes inactive.
+    pub duration: Option<i64>,
+    #[serde(flatten)]
+    pub attribution: Attribution,
+    /// Id of the custom source the program's events come from.
+    pub source: Option<String>,
+    /// Monotonic milliseconds when the event was emitted, unaffected by clock changes.
+    pub monotonic: u64,
+}
+
+/// A program in the watcher's pool.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgramStatus {
+    pub path: String,
+    pub active_since: i64,
+    pub reason: Activation,
+    /// Id of the custom source the program's events come from.
+    pub source: Option<String>,
+    /// Milliseconds until the program times out without input, `None` while playing audio,
+    /// for manual activities and while one runs.
+    pub timeout_remaining: Option<u64>,
+    #[serde(flatten)]
+    pub attribution: Attribution,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatcherState {
+    /// Whether tracking runs, it needs to be resumed and inside the schedule.
+    pub running: bool,
+    pub resumed: bool,
+    /// End of a suspension for a duration.
+    pub suspended_until: Option<i64>,
+    pub scheduled: bool,
+    pub backends: Vec<BackendStatus>,
+    pub pool: Vec<ProgramStatus>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_status() {
+        let status = WindowStatus {
+            version: WINDOW_STATUS_VERSION,
+            path: "/usr/bin/code".into(),
+            active: false,
+            time: 5_000,
+            reason: StatusReason::ended(Reason::Inactive, true),
+            is_audio: true,
+            pid: Some(42),
+            active_since: 2_000,
+            duration: Some(3_000),
+            attribution: Attribution {
+                project: Some("watcher".into()),
+                ..Default::default()
+            },
+            source: None,
+            monotonic: 100,
+        };
+        let value = serde_json::to_value(status).unwrap();
+        assert_eq!(value["version"], 2);
+        assert_eq!(value["reason"], "audio_stop");
+        assert_eq!(value["isAudio"], true);
+        assert_eq!(value["activeSince"], 2_000);
+        assert_eq!(value["project"], "watcher");
+    }
+}
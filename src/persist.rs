@@ -0,0 +1,70 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+
+/// Whether tracking runs, kept across restarts of the app.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedState {
+    pub running: bool,
+    /// End of a suspension for a duration, in milliseconds.
+    pub suspended_until: Option<i64>,
+}
+
+pub struct StateFile {
+    path: PathBuf,
+}
+
+impl StateFile {
+    /// The state file in the plugin's directory inside `app_data_dir`.
+    pub fn new(app_data_dir: &Path) -> Self {
+        Self {
+            path: app_data_dir.join("shion-watcher").join("state.json"),
+        }
+    }
+
+    /// The saved state, `None` before the first save.
+    pub fn load(&self) -> Result<Option<SavedState>> {
+        match fs::read(&self.path) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, state: &SavedState) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // a crash while writing keeps the previous state
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(state)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_file() {
+        let dir = std::env::temp_dir().join(format!("shion-watcher-state-{}", std::process::id()));
+        let file = StateFile::new(&dir);
+        assert_eq!(file.load().unwrap(), None);
+
+        let state = SavedState {
+            running: false,
+            suspended_until: Some(1_700_000_000_000),
+        };
+        file.save(&state).unwrap();
+        assert_eq!(file.load().unwrap(), Some(state));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+/// Time of the last keyboard or mouse input seen by the input hook, in milliseconds.
+static LAST_INPUT: AtomicI64 = AtomicI64::new(0);
+
+pub fn record_input() {
+    LAST_INPUT.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+}
+
+/// Milliseconds since the last input according to the OS.
+#[cfg(target_os = "windows")]
+pub fn os_idle_time() -> Option<i64> {
+    crate::windows::get_idle_time()
+}
+
+#[cfg(target_os = "linux")]
+pub fn os_idle_time() -> Option<i64> {
+    crate::linux::get_idle_time()
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub fn os_idle_time() -> Option<i64> {
+    None
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PresenceConfig {
+    /// Seconds without input before the user is idle.
+    pub idle_after: u64,
+    /// Seconds without input before the user is away.
+    pub away_after: u64,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            idle_after: 60,
+            away_after: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PresenceState {
+    Active,
+    Idle,
+    Away,
+    Locked,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresenceStatus {
+    pub state: PresenceState,
+    /// When the state started, for idle and away the last input.
+    pub since: i64,
+}
+
+/// Whether the user is at the computer, independent of the tracked programs.
+pub struct Presence {
+    config: PresenceConfig,
+    status: PresenceStatus,
+    started: i64,
+    locked: bool,
+    sleeping: bool,
+}
+
+impl Presence {
+    pub fn new(config: &PresenceConfig, now: i64) -> Self {
+        Self {
+            config: config.clone(),
+            status: PresenceStatus {
+                state: PresenceState::Active,
+                since: now,
+            },
+            started: now,
+            locked: false,
+            sleeping: false,
+        }
+    }
+
+    pub fn status(&self) -> PresenceStatus {
+        self.status.clone()
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.status.state == PresenceState::Locked
+    }
+
+    /// The screen was locked or the session switched away, or back.
+    pub fn set_locked(&mut self, now: i64, locked: bool) -> Option<PresenceStatus> {
+        self.locked = locked;
+        self.lock_changed(now)
+    }
+
+    /// Waking up does not unlock a session that was locked before sleeping.
+    pub fn set_sleeping(&mut self, now: i64, sleeping: bool) -> Option<PresenceStatus> {
+        self.sleeping = sleeping;
+        self.lock_changed(now)
+    }
+
+    fn lock_changed(&mut self, now: i64) -> Option<PresenceStatus> {
+        let state = if self.locked || self.sleeping {
+            PresenceState::Locked
+        } else if self.is_locked() {
+            // until the next update, which looks at the idle time
+            PresenceState::Active
+        } else {
+            return None;
+        };
+        if state == self.status.state {
+            return None;
+        }
+        self.status = PresenceStatus { state, since: now };
+        Some(self.status())
+    }
+
+    /// Milliseconds without input, from the input hook and `os_idle` when the OS reports it.
+    pub fn idle_time(&self, now: i64, os_idle: Option<i64>) -> i64 {
+        let hook_idle = now - LAST_INPUT.load(Ordering::Relaxed).max(self.started);
+        os_idle.map_or(hook_idle, |os_idle| hook_idle.min(os_idle))
+    }
+
+    /// Re-evaluates the state after `idle` milliseconds without input, returning the
+    /// new status if it changed.
+    pub fn update(&mut self, now: i64, idle: i64) -> Option<PresenceStatus> {
+        if self.status.state == PresenceState::Locked {
+            return None;
+        }
+        let state = if idle < self.config.idle_after as i64 * 1000 {
+            PresenceState::Active
+        } else if idle < self.config.away_after as i64 * 1000 {
+            PresenceState::Idle
+        } else {
+            PresenceState::Away
+        };
+        if state == self.status.state {
+            return None;
+        }
+        let since = match (self.status.state, state) {
+            // idle turning into away keeps the time of the last input
+            (PresenceState::Idle, PresenceState::Away) => self.status.since,
+            _ => now - idle,
+        };
+        self.status = PresenceStatus { state, since };
+        Some(self.status())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update() {
+        let mut presence = Presence::new(&PresenceConfig::default(), 0);
+        assert!(presence.update(30_000, 30_000).is_none());
+
+        let status = presence.update(90_000, 90_000).unwrap();
+        assert_eq!(status.state, PresenceState::Idle);
+        assert_eq!(status.since, 0);
+        let status = presence.update(400_000, 400_000).unwrap();
+        assert_eq!(status.state, PresenceState::Away);
+        assert_eq!(status.since, 0);
+
+        let status = presence.update(500_000, 2_000).unwrap();
+        assert_eq!(status.state, PresenceState::Active);
+        assert_eq!(status.since, 498_000);
+    }
+
+    #[test]
+    fn test_lock() {
+        let mut presence = Presence::new(&PresenceConfig::default(), 0);
+        let status = presence.set_locked(1_000, true).unwrap();
+        assert_eq!(status.state, PresenceState::Locked);
+        assert!(presence.update(500_000, 500_000).is_none());
+        assert!(presence.set_sleeping(2_000, true).is_none());
+
+        // still locked after waking up
+        assert!(presence.set_sleeping(3_000, false).is_none());
+        let status = presence.set_locked(4_000, false).unwrap();
+        assert_eq!(status.state, PresenceState::Active);
+        assert_eq!(status.since, 4_000);
+    }
+
+    #[test]
+    fn test_idle_time() {
+        let presence = Presence::new(&PresenceConfig::default(), 0);
+        assert_eq!(presence.idle_time(10_000, None), 10_000);
+        assert_eq!(presence.idle_time(10_000, Some(4_000)), 4_000);
+    }
+}
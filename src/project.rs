@@ -0,0 +1,212 @@
+use anyhow::anyhow;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{
+    rules::{quote, quote_list, Rule},
+    Result,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProjectConfig {
+    /// Use the built-in title rules for common editors.
+    pub presets: bool,
+    /// User rules, tried before the presets.
+    pub rules: Vec<ProjectRuleConfig>,
+}
+
+impl Default for ProjectConfig {
+    fn default() -> Self {
+        Self {
+            presets: true,
+            rules: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectRuleConfig {
+    /// Executable names (without extension, case-insensitive) the rule applies to.
+    /// An empty list applies the rule to every program.
+    #[serde(default)]
+    pub programs: Vec<String>,
+    /// Regex matched against the window title. The project is taken from the
+    /// `project` named group, or the first group if there is none.
+    pub pattern: String,
+}
+
+const PRESETS: &[(&[&str], &str)] = &[
+    // `file - workspace - Visual Studio Code`, `—` on macOS, optional remote suffix
+    (
+        &[
+            "code",
+            "code - insiders",
+            "code-insiders",
+            "codium",
+            "vscodium",
+            "code-oss",
+        ],
+        r"^(?:.* [—-] )?(?P<project>.+?)(?: \[[^\]]+\])? [—-] (?:Visual Studio Code(?: - Insiders)?|VSCodium)$",
+    ),
+    // `project – file`, older versions `project [path] – file`
+    (
+        &[
+            "idea",
+            "idea64",
+            "clion",
+            "clion64",
+            "pycharm",
+            "pycharm64",
+            "webstorm",
+            "webstorm64",
+            "goland",
+            "goland64",
+            "rider",
+            "rider64",
+            "phpstorm",
+            "phpstorm64",
+            "rubymine",
+            "rubymine64",
+            "datagrip",
+            "datagrip64",
+            "rustrover",
+            "rustrover64",
+            "studio",
+            "studio64",
+        ],
+        r"^(?P<project>[^–\[]+?)(?: \[[^\]]*\])? – .+$",
+    ),
+    // `file (project) - Sublime Text`
+    (
+        &["sublime_text", "subl"],
+        r"\((?P<project>[^()]+)\) - Sublime Text",
+    ),
+];
+
+/// Below the user's rules, which default to priority 0.
+const PRESET_PRIORITY: i32 = -10;
+
+fn rule(source: &str, priority: i32, programs: &[String], pattern: &str) -> Result<Rule> {
+    let group = if Regex::new(pattern)?
+        .capture_names()
+        .any(|name| name == Some("project"))
+    {
+        "$project"
+    } else {
+        "$1"
+    };
+    let mut line = priority.to_string();
+    if !programs.is_empty() {
+        let programs: Vec<String> = programs.iter().map(|p| p.to_lowercase()).collect();
+        line += &format!(" name={}", quote_list(&programs));
+    }
+    line += &format!(" title~{} => project={}", quote(pattern), group);
+    Rule::parse(source, &line)?
+        .ok_or_else(|| anyhow!("{}: no rule generated from {}", source, line).into())
+}
+
+/// The project config as rules extracting project names from window titles.
+pub fn rules(config: &ProjectConfig) -> Result<Vec<Rule>> {
+    let mut rules = vec![];
+    for (i, r) in config.rules.iter().enumerate() {
+        let source = format!("project.rules[{}]", i);
+        rules.push(rule(&source, 0, &r.programs, &r.pattern)?);
+    }
+    if config.presets {
+        for (programs, pattern) in PRESETS {
+            let programs: Vec<String> = programs.iter().map(|p| p.to_string()).collect();
+            rules.push(rule(
+                "project.presets",
+                PRESET_PRIORITY,
+                &programs,
+                pattern,
+            )?);
+        }
+    }
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        category::CategoryConfig,
+        config::Config,
+        rules::{Rules, Subject},
+    };
+
+    fn matcher(config: ProjectConfig) -> impl Fn(&str, &str) -> Option<String> {
+        let rules = Rules::new(&Config {
+            project: config,
+            category: CategoryConfig {
+                desktop_entries: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+        move |path, title| {
+            rules
+                .classify(&Subject {
+                    path,
+                    title: Some(title),
+                    domain: None,
+                })
+                .project
+        }
+    }
+
+    fn resolve(path: &str, title: &str) -> Option<String> {
+        matcher(ProjectConfig::default())(path, title)
+    }
+
+    #[test]
+    fn test_vscode() {
+        let path = r"C:\Users\me\AppData\Local\Programs\Microsoft VS Code\Code.exe";
+        assert_eq!(
+            resolve(path, "main.rs - shion-watcher - Visual Studio Code"),
+            Some("shion-watcher".into())
+        );
+        assert_eq!(
+            resolve(path, "● lib.rs — shion — Visual Studio Code"),
+            Some("shion".into())
+        );
+        assert_eq!(
+            resolve(path, "watcher [SSH: dev] - Visual Studio Code"),
+            Some("watcher".into())
+        );
+        assert_eq!(
+            resolve("/usr/bin/firefox", "a - b - Visual Studio Code"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_jetbrains() {
+        let path = "/opt/clion/bin/clion";
+        assert_eq!(resolve(path, "engine – main.cpp"), Some("engine".into()));
+        assert_eq!(
+            resolve(path, "engine [~/work/engine] – src/main.cpp"),
+            Some("engine".into())
+        );
+    }
+
+    #[test]
+    fn test_user_rule() {
+        let config = ProjectConfig {
+            presets: false,
+            rules: vec![ProjectRuleConfig {
+                programs: vec![],
+                pattern: r"^\[(\w+)\]".into(),
+            }],
+        };
+        let resolve = matcher(config);
+        assert_eq!(
+            resolve("/bin/any", "[billing] notes"),
+            Some("billing".into())
+        );
+        assert_eq!(resolve("/usr/bin/code", "a - b - Visual Studio Code"), None);
+    }
+}
@@ -0,0 +1,673 @@
+use std::{cmp::Reverse, collections::HashMap, fmt, fs, path::Path};
+
+use anyhow::anyhow;
+use glob::{MatchOptions, Pattern};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{category, config::Config, ignore, project, shared::program_name, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Path,
+    Name,
+    Title,
+    Domain,
+    Project,
+}
+
+impl Field {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "path" => Some(Self::Path),
+            "name" => Some(Self::Name),
+            "title" => Some(Self::Title),
+            "domain" => Some(Self::Domain),
+            "project" => Some(Self::Project),
+            _ => None,
+        }
+    }
+}
+
+enum Matcher {
+    /// `field=a|b`
+    Any(Vec<String>),
+    /// `field~regex`
+    Regex(Regex),
+    /// `field*=glob`
+    Glob(Pattern),
+}
+
+struct Condition {
+    field: Field,
+    matcher: Matcher,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    Project(String),
+    Category(String),
+    /// Seconds without input before the program's session ends.
+    Timeout(u64),
+    /// `ignore` or `ignore=false`, to track a program a broader rule ignores.
+    Ignore(bool),
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Project(project) => write!(f, "project={}", quote(project)),
+            Self::Category(category) => write!(f, "category={}", quote(category)),
+            Self::Timeout(timeout) => write!(f, "timeout={}", timeout),
+            Self::Ignore(true) => write!(f, "ignore"),
+            Self::Ignore(false) => write!(f, "ignore=false"),
+        }
+    }
+}
+
+/// One line of the rule DSL:
+///
+/// ```text
+/// [priority] condition... => action...
+/// 10 name=code|codium title~"^(?P<project>.+) - Visual Studio Code$" => project=$project
+/// domain=youtube.com => category=Entertainment timeout=600
+/// ```
+///
+/// Conditions are `field=a|b` (any of the values), `field~regex` or `field*=glob` on
+/// `path`, `name`, `title`, `domain` (also matching subdomains) and `project`. Actions
+/// are `project=`, which may use `$group` from the regexes, `category=`, `timeout=` in
+/// seconds and `ignore`, which keeps the program from being tracked at all.
+/// Rules are tried by descending priority (default 0), the first rule setting an
+/// action wins. Values with spaces are double-quoted, `#` starts a comment.
+pub struct Rule {
+    priority: i32,
+    source: String,
+    text: String,
+    conditions: Vec<Condition>,
+    actions: Vec<Action>,
+}
+
+/// Quotes `value` if needed to be read back as one DSL value.
+pub fn quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && !value.starts_with('#')
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '\\');
+    if plain {
+        return value.to_string();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Quotes a list of values for a `field=a|b` condition.
+pub fn quote_list(values: &[String]) -> String {
+    quote(&values.join("|"))
+}
+
+fn unquote(value: &str) -> String {
+    let inner = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) => inner,
+        None => return value.to_string(),
+    };
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        // only `\"` and `\\` are escapes, so regexes keep their backslashes
+        match chars.next() {
+            Some(next @ ('"' | '\\')) => result.push(next),
+            Some(next) => {
+                result.push('\\');
+                result.push(next);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Splits on whitespace outside of double quotes, dropping comments.
+fn tokenize(line: &str) -> std::result::Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quoted => {
+                token.push(c);
+                if let Some(next) = chars.next() {
+                    token.push(next);
+                }
+            }
+            '"' => {
+                quoted = !quoted;
+                token.push(c);
+            }
+            '#' if !quoted && token.is_empty() => break,
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quote".into());
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn parse_condition(token: &str) -> std::result::Result<Condition, String> {
+    let index = token
+        .find(|c: char| !c.is_ascii_lowercase())
+        .unwrap_or(token.len());
+    let (field, rest) = token.split_at(index);
+    let (op, value) = ["*=", "=", "~"]
+        .iter()
+        .find_map(|op| rest.strip_prefix(op).map(|value| (*op, unquote(value))))
+        .ok_or_else(|| {
+            format!(
+                "expected `field=value`, `field~regex` or `field*=glob`, got `{}`",
+                token
+            )
+        })?;
+    let field = Field::parse(field).ok_or_else(|| format!("unknown field `{}`", field))?;
+    let matcher = if op == "~" {
+        Matcher::Regex(Regex::new(&value).map_err(|err| err.to_string())?)
+    } else if op == "*=" {
+        Matcher::Glob(Pattern::new(&value).map_err(|err| err.to_string())?)
+    } else {
+        // Windows paths are case-insensitive
+        let lowercase =
+            matches!(field, Field::Name | Field::Domain) || (field == Field::Path && cfg!(windows));
+        Matcher::Any(
+            value
+                .split('|')
+                .map(|v| {
+                    if lowercase {
+                        v.to_lowercase()
+                    } else {
+                        v.to_string()
+                    }
+                })
+                .collect(),
+        )
+    };
+    Ok(Condition { field, matcher })
+}
+
+fn parse_action(token: &str) -> std::result::Result<Action, String> {
+    if token == "ignore" {
+        return Ok(Action::Ignore(true));
+    }
+    let (key, value) = token
+        .split_once('=')
+        .ok_or_else(|| format!("expected `action=value`, got `{}`", token))?;
+    let value = unquote(value);
+    match key {
+        "project" => Ok(Action::Project(value)),
+        "category" => Ok(Action::Category(value)),
+        "timeout" => value
+            .parse()
+            .map(Action::Timeout)
+            .map_err(|_| format!("invalid timeout `{}`", value)),
+        "ignore" => value
+            .parse()
+            .map(Action::Ignore)
+            .map_err(|_| format!("invalid ignore `{}`", value)),
+        _ => Err(format!("unknown action `{}`", key)),
+    }
+}
+
+impl Rule {
+    /// Parses one line, `None` for blank lines and comments.
+    pub fn parse(source: impl Into<String>, line: &str) -> Result<Option<Self>> {
+        let source = source.into();
+        Self::parse_tokens(source.clone(), line)
+            .map_err(|err| anyhow!("{}: {}", source, err).into())
+    }
+
+    fn parse_tokens(source: String, line: &str) -> std::result::Result<Option<Self>, String> {
+        let mut tokens = tokenize(line)?;
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+        let priority = match tokens[0].parse() {
+            Ok(priority) => {
+                tokens.remove(0);
+                priority
+            }
+            Err(_) => 0,
+        };
+        let arrow = tokens
+            .iter()
+            .position(|t| t == "=>")
+            .ok_or("missing `=>`")?;
+        let conditions = tokens[..arrow]
+            .iter()
+            .map(|t| parse_condition(t))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let actions = tokens[arrow + 1..]
+            .iter()
+            .map(|t| parse_action(t))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if actions.is_empty() {
+            return Err("missing action".into());
+        }
+        // projects are resolved before the other actions, see `Rules::explain`
+        let sets_project = actions.iter().any(|a| matches!(a, Action::Project(_)));
+        if sets_project && conditions.iter().any(|c| c.field == Field::Project) {
+            return Err("a rule setting the project cannot match on it".into());
+        }
+        Ok(Some(Self {
+            priority,
+            source,
+            text: line.trim().to_string(),
+            conditions,
+            actions,
+        }))
+    }
+
+    /// Captured regex groups if every condition holds.
+    fn matches(
+        &self,
+        subject: &Subject,
+        name: &str,
+        project: Option<&str>,
+    ) -> Option<HashMap<String, String>> {
+        let mut captures = HashMap::new();
+        for condition in &self.conditions {
+            let value = match condition.field {
+                Field::Path => Some(subject.path),
+                Field::Name => Some(name),
+                Field::Title => subject.title,
+                Field::Domain => subject.domain,
+                Field::Project => project,
+            }?;
+            match &condition.matcher {
+                Matcher::Any(values) => {
+                    let found = values.iter().any(|v| match condition.field {
+                        Field::Domain => {
+                            let value = value.to_lowercase();
+                            value == *v || value.ends_with(&format!(".{}", v))
+                        }
+                        Field::Path if cfg!(windows) => value.to_lowercase() == *v,
+                        _ => value == v,
+                    });
+                    if !found {
+                        return None;
+                    }
+                }
+                Matcher::Glob(pattern) => {
+                    let options = MatchOptions {
+                        case_sensitive: !cfg!(windows),
+                        ..Default::default()
+                    };
+                    if !pattern.matches_with(value, options) {
+                        return None;
+                    }
+                }
+                Matcher::Regex(regex) => {
+                    let found = regex.captures(value)?;
+                    for (i, group) in regex.capture_names().enumerate() {
+                        if let Some(m) = found.get(i) {
+                            let key = group.map_or_else(|| i.to_string(), str::to_string);
+                            captures.insert(key, m.as_str().to_string());
+                        }
+                    }
+                }
+            }
+        }
+        Some(captures)
+    }
+}
+
+/// Replaces `$group` and `${group}` with captured groups, `$$` with `$`.
+fn expand(template: &str, captures: &HashMap<String, String>) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(index) = rest.find('$') {
+        result.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+        let (key, len) = if let Some(braced) = rest.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => (braced, braced.len() + 1),
+            }
+        } else if rest.starts_with('$') {
+            result.push('$');
+            rest = &rest[1..];
+            continue;
+        } else {
+            let end = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            (&rest[..end], end)
+        };
+        if let Some(value) = captures.get(key) {
+            result.push_str(value);
+        }
+        rest = &rest[len..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// What the rules are evaluated against.
+pub struct Subject<'a> {
+    pub path: &'a str,
+    pub title: Option<&'a str>,
+    pub domain: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Classification {
+    pub project: Option<String>,
+    pub category: Option<String>,
+    /// Seconds without input before the session ends.
+    pub timeout: Option<u64>,
+    pub ignored: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Evaluation {
+    /// Where the rule comes from, e.g. `rules.txt:3` or `project.presets`.
+    pub source: String,
+    pub priority: i32,
+    pub rule: String,
+    pub matched: bool,
+    /// Actions of the rule that took effect, with `$group` expanded.
+    pub applied: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Explanation {
+    pub classification: Classification,
+    /// Every rule, in evaluation order.
+    pub rules: Vec<Evaluation>,
+}
+
+/// Priority-ordered rules from the rule file and the `project`, `category` and `ignore` config.
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+impl Rules {
+    pub fn new(config: &Config) -> Result<Self> {
+        let mut rules = vec![];
+        if let Some(path) = &config.rules {
+            rules.extend(load(path)?);
+        }
+        rules.extend(project::rules(&config.project)?);
+        rules.extend(category::rules(&config.category)?);
+        rules.extend(ignore::rules(&config.ignore)?);
+        Ok(Self::from_rules(rules))
+    }
+
+    fn from_rules(mut rules: Vec<Rule>) -> Self {
+        // stable, so equal priorities keep their order
+        rules.sort_by_key(|rule| Reverse(rule.priority));
+        Self { rules }
+    }
+
+    /// The index of the first rule setting a project and the project, resolved before the
+    /// other actions so that rules can match on it.
+    fn project(&self, subject: &Subject, name: &str) -> Option<(usize, String)> {
+        for (index, rule) in self.rules.iter().enumerate() {
+            let template = rule.actions.iter().find_map(|action| match action {
+                Action::Project(template) => Some(template),
+                _ => None,
+            });
+            let template = match template {
+                Some(template) => template,
+                None => continue,
+            };
+            if let Some(captures) = rule.matches(subject, name, None) {
+                let value = expand(template, &captures).trim().to_string();
+                if !value.is_empty() {
+                    return Some((index, value));
+                }
+            }
+        }
+        None
+    }
+
+    /// The classification `explain` gives, without recording each rule.
+    pub fn classify(&self, subject: &Subject) -> Classification {
+        let name = program_name(subject.path);
+        let mut classification = Classification {
+            project: self.project(subject, &name).map(|(_, value)| value),
+            ..Default::default()
+        };
+        let mut ignored = None;
+        for rule in &self.rules {
+            if classification.category.is_some()
+                && classification.timeout.is_some()
+                && ignored.is_some()
+            {
+                break;
+            }
+            // rules whose actions are all taken already cannot change anything
+            let useful = rule.actions.iter().any(|action| match action {
+                Action::Project(_) => false,
+                Action::Category(_) => classification.category.is_none(),
+                Action::Timeout(_) => classification.timeout.is_none(),
+                Action::Ignore(_) => ignored.is_none(),
+            });
+            if !useful
+                || rule
+                    .matches(subject, &name, classification.project.as_deref())
+                    .is_none()
+            {
+                continue;
+            }
+            for action in &rule.actions {
+                match action {
+                    Action::Project(_) => {}
+                    Action::Category(category) => {
+                        classification
+                            .category
+                            .get_or_insert_with(|| category.clone());
+                    }
+                    Action::Timeout(timeout) => {
+                        classification.timeout.get_or_insert(*timeout);
+                    }
+                    Action::Ignore(ignore) => {
+                        ignored.get_or_insert(*ignore);
+                    }
+                }
+            }
+        }
+        classification.ignored = ignored.unwrap_or(false);
+        classification
+    }
+
+    /// Every rule with whether it matched and which of its actions took effect.
+    pub fn explain(&self, subject: &Subject) -> Explanation {
+        let name = program_name(subject.path);
+        let project = self.project(subject, &name);
+
+        let mut classification = Classification {
+            project: project.as_ref().map(|(_, value)| value.clone()),
+            ..Default::default()
+        };
+        let mut ignored = None;
+        let mut evaluations = vec![];
+        for (index, rule) in self.rules.iter().enumerate() {
+            let matched = rule
+                .matches(subject, &name, classification.project.as_deref())
+                .is_some();
+            let mut applied = vec![];
+            for action in rule.actions.iter().filter(|_| matched) {
+                match action {
+                    Action::Project(_) => {
+                        if let Some((_, value)) = project.as_ref().filter(|(i, _)| *i == index) {
+                            applied.push(Action::Project(value.clone()).to_string());
+                        }
+                    }
+                    Action::Category(category) => {
+                        if classification.category.is_none() {
+                            classification.category = Some(category.clone());
+                            applied.push(action.to_string());
+                        }
+                    }
+                    Action::Timeout(timeout) => {
+                        if classification.timeout.is_none() {
+                            classification.timeout = Some(*timeout);
+                            applied.push(action.to_string());
+                        }
+                    }
+                    Action::Ignore(ignore) => {
+                        if ignored.is_none() {
+                            ignored = Some(*ignore);
+                            applied.push(action.to_string());
+                        }
+                    }
+                }
+            }
+            evaluations.push(Evaluation {
+                source: rule.source.clone(),
+                priority: rule.priority,
+                rule: rule.text.clone(),
+                matched,
+                applied,
+            });
+        }
+        classification.ignored = ignored.unwrap_or(false);
+        Explanation {
+            classification,
+            rules: evaluations,
+        }
+    }
+}
+
+fn load(path: &Path) -> Result<Vec<Rule>> {
+    let content = fs::read_to_string(path)?;
+    let mut rules = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let source = format!("{}:{}", path.display(), i + 1);
+        rules.extend(Rule::parse(source, line)?);
+    }
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(lines: &[&str]) -> Rules {
+        let rules = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| Rule::parse(format!("test:{}", i + 1), line).unwrap())
+            .collect();
+        Rules::from_rules(rules)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(Rule::parse("test", "  # comment").unwrap().is_none());
+        assert!(Rule::parse("test", "name=code").is_err());
+        assert!(Rule::parse("test", "name=code => colour=red").is_err());
+        assert!(Rule::parse("test", "title~\"unterminated => project=x").is_err());
+        assert!(Rule::parse("test", "project=a => project=b").is_err());
+        let err = Rule::parse("rules.txt:3", "size=1 => timeout=1")
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "rules.txt:3: unknown field `size`");
+
+        let pattern = r#"^"(?P<project>[^"]+)" \d+$"#;
+        let rule = Rule::parse(
+            "test",
+            &format!("title~{} => project=$project", quote(pattern)),
+        )
+        .unwrap()
+        .unwrap();
+        match &rule.conditions[0].matcher {
+            Matcher::Regex(regex) => assert_eq!(regex.as_str(), pattern),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_explain() {
+        let rules = rules(&[
+            "name=firefox => category=Research",
+            "10 domain=youtube.com => category=Entertainment timeout=600",
+            r#"title~"^\[(?P<client>\w+)\] " => project="client ${client}""#,
+            "project=\"client acme\" => category=Work",
+        ]);
+        let subject = Subject {
+            path: "/usr/lib/firefox/firefox",
+            title: Some("[acme] Invoice - Mozilla Firefox"),
+            domain: Some("m.youtube.com"),
+        };
+        let explanation = rules.explain(&subject);
+        assert_eq!(
+            explanation.classification,
+            Classification {
+                project: Some("client acme".into()),
+                category: Some("Entertainment".into()),
+                timeout: Some(600),
+                ignored: false,
+            }
+        );
+        // priority first, then file order
+        let sources: Vec<&str> = explanation
+            .rules
+            .iter()
+            .map(|e| e.source.as_str())
+            .collect();
+        assert_eq!(sources, ["test:2", "test:1", "test:3", "test:4"]);
+        assert!(explanation.rules.iter().all(|e| e.matched));
+        assert_eq!(
+            explanation.rules[0].applied,
+            ["category=Entertainment", "timeout=600"]
+        );
+        assert!(explanation.rules[1].applied.is_empty());
+        assert_eq!(explanation.rules[2].applied, ["project=\"client acme\""]);
+        assert_eq!(rules.classify(&subject), explanation.classification);
+
+        let subject = Subject {
+            path: "/usr/lib/firefox/firefox",
+            title: None,
+            domain: Some("docs.rs"),
+        };
+        let classification = rules.classify(&subject);
+        assert_eq!(classification, rules.explain(&subject).classification);
+        assert_eq!(classification.project, None);
+        assert_eq!(classification.category.as_deref(), Some("Research"));
+    }
+
+    #[test]
+    fn test_ignore() {
+        let rules = rules(&[
+            "path*=/opt/** => ignore",
+            "1 name=tool title~Report => ignore=false",
+        ]);
+        let ignored = |path, title| {
+            rules
+                .classify(&Subject {
+                    path,
+                    title: Some(title),
+                    domain: None,
+                })
+                .ignored
+        };
+        assert!(ignored("/opt/tool/tool", "Settings"));
+        assert!(!ignored("/opt/tool/tool", "Report 2024"));
+        assert!(!ignored("/usr/bin/tool", "Settings"));
+    }
+}
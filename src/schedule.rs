@@ -0,0 +1,131 @@
+use anyhow::anyhow;
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+
+/// A weekly stretch of time in which tracking runs, e.g. Mon–Fri 09:00–18:00.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleWindow {
+    /// `mon` to `sun`, every day when empty.
+    #[serde(default)]
+    pub days: Vec<String>,
+    /// Local time as `HH:MM`.
+    pub start: String,
+    /// Local time as `HH:MM`, an end before the start runs into the next day.
+    pub end: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScheduleCause {
+    /// A schedule window opened or closed.
+    Schedule,
+    /// A suspension for a duration or until a time ended.
+    Timer,
+}
+
+/// Tracking paused or resumed without being asked to.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleChange {
+    pub cause: ScheduleCause,
+    /// Whether tracking runs now, a manual suspension outlives the schedule.
+    pub running: bool,
+    pub time: i64,
+}
+
+struct Window {
+    days: Vec<Weekday>,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl Window {
+    fn parse(window: &ScheduleWindow) -> Result<Self> {
+        let days = window
+            .days
+            .iter()
+            .map(|day| {
+                day.parse::<Weekday>()
+                    .map_err(|_| anyhow!("schedule: unknown day `{}`", day))
+            })
+            .collect::<std::result::Result<_, _>>()?;
+        let time = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .map_err(|_| anyhow!("schedule: invalid time `{}`", time))
+        };
+        Ok(Self {
+            days,
+            start: time(&window.start)?,
+            end: time(&window.end)?,
+        })
+    }
+
+    fn on(&self, day: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&day)
+    }
+
+    fn contains(&self, at: NaiveDateTime) -> bool {
+        let time = at.time();
+        if self.start <= self.end {
+            return self.on(at.weekday()) && self.start <= time && time < self.end;
+        }
+        // overnight, the window belongs to the day it starts on
+        let yesterday = (at - Duration::days(1)).weekday();
+        (self.on(at.weekday()) && time >= self.start) || (self.on(yesterday) && time < self.end)
+    }
+}
+
+/// The weekly windows in which tracking runs.
+pub struct Schedule {
+    windows: Vec<Window>,
+}
+
+impl Schedule {
+    pub fn new(windows: &[ScheduleWindow]) -> Result<Self> {
+        Ok(Self {
+            windows: windows.iter().map(Window::parse).collect::<Result<_>>()?,
+        })
+    }
+
+    /// Whether tracking runs at the local time `at`, always without windows.
+    pub fn allows(&self, at: NaiveDateTime) -> bool {
+        self.windows.is_empty() || self.windows.iter().any(|w| w.contains(at))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(days: &[&str], start: &str, end: &str) -> ScheduleWindow {
+        ScheduleWindow {
+            days: days.iter().map(|d| d.to_string()).collect(),
+            start: start.into(),
+            end: end.into(),
+        }
+    }
+
+    #[test]
+    fn test_schedule() {
+        let schedule = Schedule::new(&[
+            window(&["mon", "tue", "wed", "thu", "fri"], "09:00", "18:00"),
+            window(&["sat"], "22:00", "02:00"),
+        ])
+        .unwrap();
+        // 2024-06-03 is a monday
+        let at = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        assert!(schedule.allows(at("2024-06-03 09:00")));
+        assert!(!schedule.allows(at("2024-06-03 18:00")));
+        assert!(!schedule.allows(at("2024-06-08 12:00")));
+        assert!(schedule.allows(at("2024-06-08 23:00")));
+        assert!(schedule.allows(at("2024-06-09 01:00")));
+        assert!(!schedule.allows(at("2024-06-09 23:00")));
+
+        assert!(Schedule::new(&[]).unwrap().allows(at("2024-06-09 23:00")));
+        assert!(Schedule::new(&[window(&["someday"], "09:00", "18:00")]).is_err());
+        assert!(Schedule::new(&[window(&[], "9am", "18:00")]).is_err());
+    }
+}
@@ -1,8 +1,111 @@
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
+const PATH_CACHE_CAPACITY: usize = 256;
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Program {
     pub path: String,
     pub name: String,
     pub icon: Vec<u8>,
 }
+
+/// Lowercased executable name without directory and extension, e.g. `code` for
+/// `C:\Program Files\Microsoft VS Code\Code.exe`.
+pub fn program_name(path: &str) -> String {
+    let file = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    let stem = match file.rfind('.') {
+        Some(index) if index > 0 => &file[..index],
+        _ => file,
+    };
+    stem.to_lowercase()
+}
+
+/// A top-level window and the program owning it.
+#[derive(Debug, Clone)]
+pub struct WindowInfo {
+    pub pid: u32,
+    pub path: String,
+    pub title: String,
+}
+
+/// Executable paths by pid. Entries are checked against the process start time, so a
+/// reused pid is not mistaken for the process that had it before.
+#[derive(Default)]
+pub struct PathCache {
+    paths: Mutex<HashMap<u32, (u64, String)>>,
+}
+
+impl PathCache {
+    pub fn resolve(
+        &self,
+        pid: u32,
+        start_time: u64,
+        resolve: impl FnOnce() -> Option<String>,
+    ) -> Option<String> {
+        let mut paths = self.paths.lock();
+        if let Some((time, path)) = paths.get(&pid) {
+            if *time == start_time {
+                return Some(path.clone());
+            }
+        }
+        let path = resolve()?;
+        if paths.len() >= PATH_CACHE_CAPACITY {
+            paths.retain(|pid, _| is_process_alive(*pid));
+            if paths.len() >= PATH_CACHE_CAPACITY {
+                paths.clear();
+            }
+        }
+        paths.insert(pid, (start_time, path.clone()));
+        Some(path)
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn is_process_alive(pid: u32) -> bool {
+    std::path::Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_process_alive(_pid: u32) -> bool {
+    true
+}
+
+/// Whether `pid` was started, directly or not, by `ancestor`.
+#[cfg(target_os = "linux")]
+pub fn is_descendant(pid: u32, ancestor: u32) -> bool {
+    let mut pid = pid;
+    // bounded in case of a parent loop while processes are being replaced
+    for _ in 0..32 {
+        match crate::linux::process::parent(pid) {
+            Some(parent) if parent == ancestor => return true,
+            Some(parent) if parent > 1 => pid = parent,
+            _ => return false,
+        }
+    }
+    false
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_descendant(_pid: u32, _ancestor: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_cache() {
+        let cache = PathCache::default();
+        let path = cache.resolve(42, 1000, || Some("/usr/bin/code".into()));
+        assert_eq!(path.as_deref(), Some("/usr/bin/code"));
+        let path = cache.resolve(42, 1000, || panic!("cached"));
+        assert_eq!(path.as_deref(), Some("/usr/bin/code"));
+        // pid reused by another process
+        let path = cache.resolve(42, 2000, || Some("/usr/bin/vim".into()));
+        assert_eq!(path.as_deref(), Some("/usr/bin/vim"));
+    }
+}
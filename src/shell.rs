@@ -0,0 +1,99 @@
+use std::{collections::HashMap, path::Path, time::Instant};
+
+use anyhow::anyhow;
+use parking_lot::Mutex;
+
+use crate::{
+    shared::{is_descendant, is_process_alive},
+    store::Activity,
+    Result,
+};
+
+const BASH: &str = include_str!("../shell/shion-watcher.bash");
+const ZSH: &str = include_str!("../shell/shion-watcher.zsh");
+const FISH: &str = include_str!("../shell/shion-watcher.fish");
+
+/// The snippet to source in `shell`, reporting to the socket at `socket`.
+pub fn integration(shell: &str, socket: &Path) -> Result<String> {
+    let snippet = match shell {
+        "bash" => BASH,
+        "zsh" => ZSH,
+        "fish" => FISH,
+        _ => return Err(anyhow!("unsupported shell: {}", shell).into()),
+    };
+    Ok(snippet.replace("@SOCKET@", &socket.to_string_lossy()))
+}
+
+struct Shell {
+    cwd: String,
+    command: Option<String>,
+    updated: Instant,
+}
+
+/// State reported by shells running the integration snippet, by shell pid.
+#[derive(Default)]
+pub struct Shells {
+    shells: Mutex<HashMap<u32, Shell>>,
+}
+
+impl Shells {
+    /// Records the state of the shell `pid`, `command` is `None` once it is back at the prompt.
+    pub fn update(&self, pid: u32, cwd: String, command: Option<String>) {
+        let mut shells = self.shells.lock();
+        shells.retain(|pid, _| is_process_alive(*pid));
+        shells.insert(
+            pid,
+            Shell {
+                cwd,
+                command,
+                updated: Instant::now(),
+            },
+        );
+    }
+
+    /// The most recently used shell running inside the process `pid`, e.g. a terminal window.
+    pub fn activity(&self, pid: u32) -> Option<Activity> {
+        let shells = self.shells.lock();
+        let (_, shell) = shells
+            .iter()
+            .filter(|(shell, _)| is_descendant(**shell, pid))
+            .max_by_key(|(_, shell)| shell.updated)?;
+        Some(Activity::Shell {
+            cwd: shell.cwd.clone(),
+            command: shell.command.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integration() {
+        let socket = Path::new("/run/user/1000/shion-watcher.sock");
+        for shell in ["bash", "zsh", "fish"] {
+            let snippet = integration(shell, socket).unwrap();
+            assert!(snippet.contains("/run/user/1000/shion-watcher.sock"));
+            assert!(!snippet.contains("@SOCKET@"));
+        }
+        assert!(integration("cmd", socket).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_activity() {
+        // the test process plays the shell, its parent the terminal
+        let shells = Shells::default();
+        let pid = std::process::id();
+        shells.update(pid, "/src".into(), Some("cargo test".into()));
+        assert_eq!(
+            shells.activity(std::os::unix::process::parent_id()),
+            Some(Activity::Shell {
+                cwd: "/src".into(),
+                command: Some("cargo test".into()),
+            })
+        );
+        assert_eq!(shells.activity(pid), None);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::watcher::{self, WatcherEvent, WATCHER_EVENT_CHANNEL};
+
+/// Something the user is doing according to a custom source.
+#[derive(Debug, Clone, Default)]
+pub struct SourceEvent {
+    /// Identifies the program, like the executable path does for windows. Ignore and
+    /// project rules match against it.
+    pub path: String,
+    /// `false` once the activity stopped.
+    pub active: bool,
+    /// Keyboard or mouse input, rather than the activity coming to the foreground.
+    pub input: bool,
+    pub is_audio: bool,
+    pub title: Option<String>,
+    pub pid: Option<u32>,
+}
+
+/// Passes the events of a source to the watcher.
+#[derive(Debug, Clone)]
+pub struct ActivitySender {
+    id: String,
+}
+
+impl ActivitySender {
+    pub(crate) fn new(id: &str) -> Self {
+        Self { id: id.to_string() }
+    }
+
+    /// Sends `event`, `false` once the watcher shut down, when the source should return.
+    pub fn send(&self, event: SourceEvent) -> bool {
+        if watcher::is_shutdown() {
+            return false;
+        }
+        WATCHER_EVENT_CHANNEL
+            .lock()
+            .0
+            .send(WatcherEvent {
+                path: event.path,
+                is_audio: event.is_audio,
+                active: event.active,
+                input: event.input,
+                title: event.title,
+                pid: event.pid,
+                source: Some(self.id.clone()),
+            })
+            .is_ok()
+    }
+}
+
+/// Activity from outside the built-in window, input and audio watchers, e.g. an IDE plugin,
+/// a game launcher or a calendar.
+pub trait ActivitySource: Send + 'static {
+    /// Unique id, e.g. `vscode`, reported with its events and as its backend.
+    fn id(&self) -> String;
+
+    /// Runs on its own thread, sending events until it returns.
+    fn run(self: Box<Self>, sender: ActivitySender) -> anyhow::Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Calendar;
+
+    impl ActivitySource for Calendar {
+        fn id(&self) -> String {
+            "calendar".into()
+        }
+
+        fn run(self: Box<Self>, sender: ActivitySender) -> anyhow::Result<()> {
+            sender.send(SourceEvent {
+                path: "calendar:standup".into(),
+                active: true,
+                title: Some("Standup".into()),
+                ..Default::default()
+            });
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_send() {
+        let source: Box<dyn ActivitySource> = Box::new(Calendar);
+        let sender = ActivitySender::new(&source.id());
+        source.run(sender).unwrap();
+        let receiver = WATCHER_EVENT_CHANNEL.lock().1.clone();
+        let event = receiver
+            .try_iter()
+            .find(|event| event.path == "calendar:standup")
+            .unwrap();
+        assert_eq!(event.source.as_deref(), Some("calendar"));
+        assert!(event.active);
+    }
+}
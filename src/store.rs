@@ -0,0 +1,328 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::{category::Category, git::Repository};
+
+/// A finer-grained activity inside a program.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Activity {
+    /// A shell running inside a terminal, `command` is `None` while at the prompt.
+    Shell {
+        cwd: String,
+        command: Option<String>,
+    },
+    /// The active tab of a browser, `url` and `title` are dropped in domain-only mode.
+    Browser {
+        domain: String,
+        url: Option<String>,
+        title: Option<String>,
+    },
+}
+
+/// What a program is being used for.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attribution {
+    pub project: Option<String>,
+    pub repository: Option<Repository>,
+    pub activity: Option<Activity>,
+    pub category: Option<Category>,
+}
+
+impl Attribution {
+    /// Whether `other` continues the same session. Browsing another page of the same domain
+    /// does, only the domain of a tab counts.
+    pub fn same_session(&self, other: &Attribution) -> bool {
+        let same_activity = match (&self.activity, &other.activity) {
+            (
+                Some(Activity::Browser { domain, .. }),
+                Some(Activity::Browser {
+                    domain: other_domain,
+                    ..
+                }),
+            ) => domain == other_domain,
+            (activity, other_activity) => activity == other_activity,
+        };
+        same_activity
+            && self.project == other.project
+            && self.repository == other.repository
+            && self.category == other.category
+    }
+}
+
+/// Why a session ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Reason {
+    /// Another program, or another project in the same program, became active.
+    Switched,
+    /// The program reported it is no longer active, e.g. audio stopped.
+    Inactive,
+    /// No input for the program's timeout.
+    Timeout,
+    /// Tracking was suspended.
+    Suspended,
+    /// The screen was locked or the session switched away.
+    Locked,
+    /// The system went to sleep.
+    Sleep,
+    /// The wall clock was set, the session continues in a new one.
+    ClockChanged,
+    /// The manual activity was stopped.
+    Stopped,
+    /// The app exited.
+    Shutdown,
+}
+
+/// A finished (or, for aggregates, still running) stretch of activity of one program.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub path: String,
+    #[serde(flatten)]
+    pub attribution: Attribution,
+    pub start: i64,
+    pub end: i64,
+    /// `None` while still running.
+    pub reason: Option<Reason>,
+}
+
+/// Where finished sessions are kept.
+pub trait Store: Send + Sync {
+    fn record(&self, session: Session);
+    fn sessions(&self) -> Vec<Session>;
+
+    /// Writes out what is buffered, once the app exits.
+    fn flush(&self) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    sessions: Mutex<Vec<Session>>,
+}
+
+impl Store for MemoryStore {
+    fn record(&self, session: Session) {
+        self.sessions.lock().push(session);
+    }
+
+    fn sessions(&self) -> Vec<Session> {
+        self.sessions.lock().clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GroupBy {
+    Program,
+    Project,
+    Repository,
+    Directory,
+    Command,
+    Domain,
+    Category,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Aggregate {
+    pub key: Option<String>,
+    /// Total time in milliseconds.
+    pub duration: i64,
+    pub count: usize,
+    /// Productivity weight of the category, only when grouping by category.
+    pub weight: Option<f64>,
+}
+
+/// Sums session durations per key, longest first. Time before `since` is not counted.
+pub fn aggregate(sessions: &[Session], group_by: GroupBy, since: Option<i64>) -> Vec<Aggregate> {
+    let mut map: HashMap<Option<String>, Aggregate> = HashMap::new();
+    for session in sessions {
+        let start = since.map_or(session.start, |since| session.start.max(since));
+        if session.end <= start {
+            continue;
+        }
+        let key = match group_by {
+            GroupBy::Program => Some(session.path.clone()),
+            GroupBy::Project => session.attribution.project.clone(),
+            GroupBy::Repository => session
+                .attribution
+                .repository
+                .as_ref()
+                .map(|r| r.root.clone()),
+            GroupBy::Directory => match &session.attribution.activity {
+                Some(Activity::Shell { cwd, .. }) => Some(cwd.clone()),
+                _ => None,
+            },
+            GroupBy::Command => match &session.attribution.activity {
+                Some(Activity::Shell { command, .. }) => command.clone(),
+                _ => None,
+            },
+            GroupBy::Domain => match &session.attribution.activity {
+                Some(Activity::Browser { domain, .. }) => Some(domain.clone()),
+                _ => None,
+            },
+            GroupBy::Category => session
+                .attribution
+                .category
+                .as_ref()
+                .map(|c| c.name.clone()),
+        };
+        let weight = match group_by {
+            GroupBy::Category => session.attribution.category.as_ref().map(|c| c.weight),
+            _ => None,
+        };
+        let entry = map.entry(key.clone()).or_insert(Aggregate {
+            key,
+            duration: 0,
+            count: 0,
+            weight,
+        });
+        entry.duration += session.end - start;
+        entry.count += 1;
+    }
+    let mut list: Vec<Aggregate> = map.into_values().collect();
+    list.sort_by_key(|a| Reverse(a.duration));
+    list
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Productivity {
+    /// Time in milliseconds in categories with a positive weight.
+    pub productive: i64,
+    /// Time in milliseconds in categories with a negative weight.
+    pub distracting: i64,
+    /// Time in milliseconds with a zero weight or no category.
+    pub neutral: i64,
+    /// Duration-weighted mean of the weights, from `-1` to `1`.
+    pub score: f64,
+}
+
+/// Splits time by productivity weight. Time before `since` is not counted.
+pub fn productivity(sessions: &[Session], since: Option<i64>) -> Productivity {
+    let mut productivity = Productivity::default();
+    let mut weighted = 0.0;
+    for session in sessions {
+        let start = since.map_or(session.start, |since| session.start.max(since));
+        if session.end <= start {
+            continue;
+        }
+        let duration = session.end - start;
+        let weight = session
+            .attribution
+            .category
+            .as_ref()
+            .map_or(0.0, |c| c.weight);
+        if weight > 0.0 {
+            productivity.productive += duration;
+        } else if weight < 0.0 {
+            productivity.distracting += duration;
+        } else {
+            productivity.neutral += duration;
+        }
+        weighted += weight * duration as f64;
+    }
+    let total = productivity.productive + productivity.distracting + productivity.neutral;
+    if total > 0 {
+        productivity.score = weighted / total as f64;
+    }
+    productivity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(path: &str, project: Option<&str>, start: i64, end: i64) -> Session {
+        Session {
+            path: path.into(),
+            attribution: Attribution {
+                project: project.map(Into::into),
+                ..Default::default()
+            },
+            start,
+            end,
+            reason: Some(Reason::Switched),
+        }
+    }
+
+    #[test]
+    fn test_same_session() {
+        let browsing = |domain: &str, url: &str| Attribution {
+            activity: Some(Activity::Browser {
+                domain: domain.into(),
+                url: Some(url.into()),
+                title: Some(url.into()),
+            }),
+            ..Default::default()
+        };
+        let docs = browsing("docs.rs", "https://docs.rs/serde");
+        assert!(docs.same_session(&browsing("docs.rs", "https://docs.rs/tauri")));
+        assert!(!docs.same_session(&browsing("github.com", "https://github.com")));
+        assert!(!docs.same_session(&Attribution {
+            project: Some("watcher".into()),
+            ..docs.clone()
+        }));
+    }
+
+    #[test]
+    fn test_aggregate_by_project() {
+        let sessions = vec![
+            session("code", Some("a"), 0, 100),
+            session("idea", Some("a"), 100, 150),
+            session("code", Some("b"), 150, 400),
+            session("firefox", None, 400, 410),
+        ];
+        let list = aggregate(&sessions, GroupBy::Project, None);
+        assert_eq!(list[0].key.as_deref(), Some("b"));
+        assert_eq!(list[0].duration, 250);
+        assert_eq!(list[1].key.as_deref(), Some("a"));
+        assert_eq!(list[1].duration, 150);
+        assert_eq!(list[1].count, 2);
+
+        let list = aggregate(&sessions, GroupBy::Program, Some(120));
+        let code = list
+            .iter()
+            .find(|a| a.key.as_deref() == Some("code"))
+            .unwrap();
+        assert_eq!(code.duration, 250);
+        let idea = list
+            .iter()
+            .find(|a| a.key.as_deref() == Some("idea"))
+            .unwrap();
+        assert_eq!(idea.duration, 30);
+    }
+
+    #[test]
+    fn test_productivity() {
+        let categorised = |start, end, name: &str, weight| {
+            let mut session = session("code", None, start, end);
+            session.attribution.category = Some(Category {
+                name: name.into(),
+                weight,
+            });
+            session
+        };
+        let sessions = vec![
+            categorised(0, 300, "Development", 1.0),
+            categorised(300, 400, "Entertainment", -1.0),
+            session("nautilus", None, 400, 500),
+        ];
+        let result = productivity(&sessions, None);
+        assert_eq!(result.productive, 300);
+        assert_eq!(result.distracting, 100);
+        assert_eq!(result.neutral, 100);
+        assert!((result.score - 0.4).abs() < 1e-9);
+
+        let list = aggregate(&sessions, GroupBy::Category, None);
+        assert_eq!(list[0].key.as_deref(), Some("Development"));
+        assert_eq!(list[0].weight, Some(1.0));
+        assert_eq!(list[0].duration, 300);
+    }
+}
@@ -1,3 +1,4 @@
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
@@ -7,6 +8,8 @@ pub struct Timer {
     callback: Arc<Mutex<Box<dyn Fn() + Send + 'static>>>,
     done: Arc<Mutex<bool>>,
     duration: Arc<Mutex<Duration>>,
+    /// Set once the timer is dropped, its thread ends.
+    stopped: Arc<AtomicBool>,
 }
 
 impl Timer {
@@ -19,6 +22,7 @@ impl Timer {
             callback: Arc::new(Mutex::new(Box::new(callback))),
             done: Arc::new(Mutex::new(false)),
             duration: Arc::new(Mutex::new(duration)),
+            stopped: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -27,13 +31,21 @@ impl Timer {
         let callback = Arc::clone(&self.callback);
         let duration = Arc::clone(&self.duration);
         let done = Arc::clone(&self.done);
+        let stopped = Arc::clone(&self.stopped);
         thread::spawn(move || loop {
-            let elapsed = start.lock().unwrap().elapsed();
-            let duration = duration.lock().unwrap();
-            let mut done = done.lock().unwrap();
-            if elapsed >= *duration {
+            if stopped.load(Ordering::Relaxed) {
+                break;
+            }
+            // the callback runs without the locks held, it may lock what `reset` is called under
+            let expired = {
+                let start = start.lock().unwrap();
+                let duration = duration.lock().unwrap();
+                let mut done = done.lock().unwrap();
+                *done = start.elapsed() >= *duration;
+                *done
+            };
+            if expired {
                 (callback.lock().unwrap())();
-                *done = true;
                 break;
             }
             thread::sleep(Duration::from_millis(1000 / 60));
@@ -44,18 +56,32 @@ impl Timer {
         let start = Arc::clone(&self.start);
         let callback = Arc::clone(&self.callback);
         let duration = Arc::clone(&self.duration);
+        let stopped = Arc::clone(&self.stopped);
         thread::spawn(move || loop {
-            let mut start = start.lock().unwrap();
-            let duration = duration.lock().unwrap();
-            let elapsed = start.elapsed();
-            if elapsed >= *duration {
+            if stopped.load(Ordering::Relaxed) {
+                break;
+            }
+            let expired = {
+                let mut start = start.lock().unwrap();
+                let expired = start.elapsed() >= *duration.lock().unwrap();
+                if expired {
+                    *start = Instant::now();
+                }
+                expired
+            };
+            if expired {
                 (callback.lock().unwrap())();
-                *start = Instant::now();
             }
             thread::sleep(Duration::from_millis(1000 / 60));
         });
     }
 
+    /// Time left until the callback runs, zero once it ran.
+    pub fn remaining(&self) -> Duration {
+        let elapsed = self.start.lock().unwrap().elapsed();
+        self.duration.lock().unwrap().saturating_sub(elapsed)
+    }
+
     pub fn reset(&self) {
         let mut start = self.start.lock().unwrap();
         let mut done = self.done.lock().unwrap();
@@ -67,6 +93,12 @@ impl Timer {
     }
 }
 
+impl Drop for Timer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
 mod tests {
     use super::*;
 
@@ -95,3 +127,43 @@ mod tests {
         loop {}
     }
 }
+
+#[cfg(test)]
+mod lifecycle_tests {
+    use super::*;
+
+    #[test]
+    fn test_drop() {
+        let called = Arc::new(AtomicBool::new(false));
+        let timer = Timer::new(Duration::from_millis(100), {
+            let called = Arc::clone(&called);
+            move || called.store(true, Ordering::Relaxed)
+        });
+        timer.timeout();
+        drop(timer);
+        thread::sleep(Duration::from_millis(300));
+        assert!(!called.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_reset_while_callback_waits() {
+        // like the watcher's pool, locked by the callback and held around `reset`
+        let pool = Arc::new(Mutex::new(()));
+        let (tx, rx) = std::sync::mpsc::channel();
+        let timer = Timer::new(Duration::ZERO, {
+            let pool = Arc::clone(&pool);
+            let tx = Mutex::new(tx);
+            move || {
+                let _ = tx.lock().unwrap().send(());
+                let _pool = pool.lock().unwrap();
+            }
+        });
+        let guard = pool.lock().unwrap();
+        timer.timeout();
+        rx.recv().unwrap();
+        timer.reset();
+        drop(guard);
+        // reset restarted the timer, which fires again
+        rx.recv().unwrap();
+    }
+}
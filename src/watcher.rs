@@ -1,16 +1,40 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
+use anyhow::anyhow;
 use chrono::prelude::*;
 use crossbeam_channel::Receiver;
 use crossbeam_channel::Sender;
 use lazy_static::lazy_static;
-use parking_lot::{Mutex, RwLock};
+use parking_lot::{Mutex, ReentrantMutex, RwLock};
 use serde::Serialize;
-use tauri::{AppHandle, Emitter, Runtime};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
 
-use crate::event;
+use crate::backend::Backends;
+use crate::browser::Browsers;
+use crate::clock::{self, Heartbeat, JumpKind};
+use crate::config::Config;
+use crate::event::{self, Hotkey};
+use crate::git::RepositoryResolver;
+use crate::ipc::{self, Message};
+use crate::journal::{Journal, Replay};
+#[cfg(target_os = "linux")]
+use crate::linux;
+use crate::models::{
+    Activation, ProgramStatus, StatusReason, WatcherState, WindowStatus, WINDOW_STATUS_VERSION,
+};
+use crate::persist::{SavedState, StateFile};
+use crate::presence::{self, Presence, PresenceStatus};
+use crate::rules::{Explanation, Rules, Subject};
+use crate::schedule::{Schedule, ScheduleCause, ScheduleChange};
+use crate::shell::{self, Shells};
+use crate::source::{ActivitySender, ActivitySource};
+use crate::store::{
+    aggregate, productivity, Activity, Aggregate, Attribution, GroupBy, Productivity, Reason,
+    Session, Store,
+};
 use crate::timer::Timer;
 #[cfg(target_os = "windows")]
 use crate::windows;
@@ -20,27 +44,140 @@ lazy_static! {
         Arc::new(Mutex::new(crossbeam_channel::unbounded()));
     pub static ref WATCHER_STATUS_CHANNEL: Arc<Mutex<(Sender<WatcherStatus>, Receiver<WatcherStatus>)>> =
         Arc::new(Mutex::new(crossbeam_channel::unbounded()));
+    pub static ref WATCHER_SESSION_CHANNEL: Arc<Mutex<(Sender<SessionEvent>, Receiver<SessionEvent>)>> =
+        Arc::new(Mutex::new(crossbeam_channel::unbounded()));
+    pub static ref WATCHER_TOGGLE_CHANNEL: Arc<Mutex<(Sender<()>, Receiver<()>)>> =
+        Arc::new(Mutex::new(crossbeam_channel::unbounded()));
+    pub static ref WATCHER_MESSAGE_CHANNEL: Arc<Mutex<(Sender<Message>, Receiver<Message>)>> =
+        Arc::new(Mutex::new(crossbeam_channel::unbounded()));
 }
 
+/// Set once the app exits, the watcher's threads end at their next iteration.
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+pub fn is_shutdown() -> bool {
+    SHUTDOWN.load(Ordering::Relaxed)
+}
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+/// Events kept for frontends catching up, e.g. after a reload.
+const JOURNAL_CAPACITY: usize = 1024;
+/// Source of manual activities, their paths start with `manual:`.
+const MANUAL_SOURCE: &str = "manual";
+
 static EVENT_STATUS_CHANGED: &'static str = "plugin:shion-watcher://status-changed";
+static EVENT_PRESENCE_CHANGED: &'static str = "plugin:shion-watcher://presence-changed";
+static EVENT_CLOCK_JUMPED: &'static str = "plugin:shion-watcher://clock-jumped";
+static EVENT_SCHEDULE_CHANGED: &'static str = "plugin:shion-watcher://schedule-changed";
+static EVENT_TOGGLED: &'static str = "plugin:shion-watcher://toggled";
+static EVENT_STATE_CHANGED: &'static str = "plugin:shion-watcher://watcher-state-changed";
 
 #[derive(Serialize, Clone)]
-struct WindowStatus {
-    path: String,
-    active: bool,
+struct Toggled {
+    /// Whether tracking was resumed, it only runs inside the schedule.
+    resumed: bool,
+    running: bool,
     time: i64,
 }
 
 pub struct Watcher<R: Runtime> {
     app: AppHandle<R>,
+    config: Config,
     pool: Mutex<Vec<Program>>,
+    /// Tracking runs while resumed and inside the schedule.
     running: RwLock<bool>,
+    resumed: RwLock<bool>,
+    /// End of a suspension for a duration.
+    suspended_until: Mutex<Option<i64>>,
+    schedule: Schedule,
+    scheduled: RwLock<bool>,
+    state_file: Option<StateFile>,
+    backends: Arc<Backends>,
+    journal: Mutex<Journal>,
+    emit_order: ReentrantMutex<()>,
+    subscribers: Mutex<Vec<Sender<WindowStatus>>>,
+    rules: Rules,
+    presence: Mutex<Presence>,
+    heartbeat: Mutex<Heartbeat>,
+    next_id: AtomicU64,
+    repositories: RepositoryResolver,
+    shells: Shells,
+    browsers: Browsers,
+    store: Box<dyn Store>,
 }
 
 struct Program {
+    id: u64,
     path: String,
     is_audio: bool,
     timer: Timer,
+    attribution: Attribution,
+    active_since: i64,
+    activation: Activation,
+    /// Last window seen for the program, used to re-attribute it.
+    title: Option<String>,
+    pid: Option<u32>,
+    source: Option<String>,
+}
+
+impl Program {
+    fn session(&self, end: i64, reason: Option<Reason>) -> Session {
+        Session {
+            path: self.path.clone(),
+            attribution: self.attribution.clone(),
+            start: self.active_since,
+            end,
+            reason,
+        }
+    }
+
+    fn window_status(&self, time: i64, reason: StatusReason, ended: bool) -> WindowStatus {
+        WindowStatus {
+            version: WINDOW_STATUS_VERSION,
+            path: self.path.clone(),
+            active: !ended,
+            time,
+            reason,
+            is_audio: self.is_audio,
+            pid: self.pid,
+            active_since: self.active_since,
+            duration: ended.then_some(time - self.active_since),
+            attribution: self.attribution.clone(),
+            source: self.source.clone(),
+            monotonic: clock::monotonic(),
+        }
+    }
+
+    fn started(&self) -> WindowStatus {
+        self.window_status(self.active_since, self.activation.into(), false)
+    }
+
+    fn ended(&self, end: i64, reason: Reason) -> WindowStatus {
+        let reason = StatusReason::ended(reason, self.is_audio);
+        self.window_status(end, reason, true)
+    }
+
+    /// Whether the program ends without input. Audio and manual activities go on without it.
+    fn needs_input(&self) -> bool {
+        !self.is_audio && self.activation != Activation::Manual
+    }
+
+    fn is_manual(&self) -> bool {
+        self.activation == Activation::Manual
+    }
+
+    fn status(&self, idle_paused: bool) -> ProgramStatus {
+        ProgramStatus {
+            path: self.path.clone(),
+            active_since: self.active_since,
+            reason: self.activation,
+            source: self.source.clone(),
+            timeout_remaining: (self.needs_input() && !idle_paused)
+                .then(|| self.timer.remaining().as_millis() as u64),
+            attribution: self.attribution.clone(),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -48,33 +185,131 @@ pub struct WatcherEvent {
     pub path: String,
     pub is_audio: bool,
     pub active: bool,
+    /// Keyboard or mouse input, rather than a window coming to the foreground.
+    pub input: bool,
+    /// Window title, if the event comes from a window.
+    pub title: Option<String>,
+    pub pid: Option<u32>,
+    /// Id of the custom source the event comes from.
+    pub source: Option<String>,
+}
+
+impl WatcherEvent {
+    fn activation(&self) -> Activation {
+        if self.is_audio {
+            Activation::Audio
+        } else if self.input {
+            Activation::Input
+        } else {
+            Activation::Focus
+        }
+    }
 }
 
 pub struct WatcherStatus {
     pub running: bool,
 }
 
+/// A change of the desktop session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEvent {
+    /// The screen was locked or another session became active.
+    Lock,
+    Unlock,
+    Sleep,
+    Wake,
+}
+
 impl<R: Runtime> Watcher<R> {
-    pub fn new(app: AppHandle<R>) -> Arc<Self> {
-        Arc::new(Self {
+    pub fn new(
+        app: AppHandle<R>,
+        config: &Config,
+        store: Box<dyn Store>,
+    ) -> crate::Result<Arc<Self>> {
+        let schedule = Schedule::new(&config.schedule)?;
+        if let Some(hotkey) = &config.input.hotkey {
+            Hotkey::parse(hotkey)?;
+        }
+        let scheduled = schedule.allows(Local::now().naive_local());
+        let state_file = match app.path().app_data_dir() {
+            Ok(dir) => Some(StateFile::new(&dir)),
+            Err(err) => {
+                warn!("no app data dir, state is not saved: {}", err);
+                None
+            }
+        };
+        let saved = match state_file.as_ref().map(StateFile::load) {
+            Some(Ok(saved)) => saved,
+            Some(Err(err)) => {
+                error!("load state error: {}", err);
+                None
+            }
+            None => None,
+        };
+        let saved = saved.unwrap_or(SavedState {
+            running: config.start_running,
+            suspended_until: None,
+        });
+        let watcher = Arc::new(Self {
             app,
+            config: config.clone(),
             pool: Mutex::new(vec![]),
             running: RwLock::new(false),
-        })
+            resumed: RwLock::new(saved.running),
+            suspended_until: Mutex::new(saved.suspended_until),
+            schedule,
+            scheduled: RwLock::new(scheduled),
+            state_file,
+            backends: Arc::new(Backends::default()),
+            journal: Mutex::new(Journal::new(JOURNAL_CAPACITY)),
+            emit_order: ReentrantMutex::new(()),
+            subscribers: Mutex::new(vec![]),
+            rules: Rules::new(config)?,
+            presence: Mutex::new(Presence::new(
+                &config.presence,
+                Utc::now().timestamp_millis(),
+            )),
+            heartbeat: Mutex::new(Heartbeat::default()),
+            next_id: AtomicU64::new(0),
+            repositories: RepositoryResolver::new(&config.repository),
+            shells: Shells::default(),
+            browsers: Browsers::new(&config.browser),
+            store,
+        });
+        watcher.update_running()?;
+        Ok(watcher)
     }
 
     pub fn run(self: &Arc<Self>) {
-        thread::spawn(|| {
-            #[cfg(target_os = "windows")]
-            if let Err(err) = windows::App::start() {
-                error!("windows watcher error: {}", err);
-            }
-        });
-        thread::spawn(|| {
-            if let Err(err) = event::run() {
-                error!("rdev error: {:?}", err);
+        #[cfg(target_os = "windows")]
+        {
+            self.backends
+                .supervise("windows watcher", windows::App::start);
+            self.backends
+                .supervise("audio watcher", windows::watch_audio);
+        }
+        #[cfg(target_os = "linux")]
+        self.backends.supervise("linux watcher", linux::App::start);
+        if self.config.input.hook {
+            let config = self.config.input.clone();
+            self.backends.supervise("input hook", move || {
+                event::run(&config).map_err(|err| anyhow!("{:?}", err))
+            });
+        } else {
+            if self.config.input.hotkey.is_some() {
+                warn!("the hotkey needs the input hook");
             }
-        });
+            self.backends.supervise("idle poll", || {
+                event::poll();
+                Ok(())
+            });
+        }
+        #[cfg(target_os = "linux")]
+        self.backends.supervise("logind", linux::logind::run);
+        if !self.config.socket.disabled {
+            let config = self.config.socket.clone();
+            self.backends.supervise("socket", move || ipc::run(&config));
+        }
 
         let timer = Timer::new(Duration::from_secs(30), {
             let watcher = Arc::clone(&self);
@@ -89,148 +324,787 @@ impl<R: Runtime> Watcher<R> {
         });
         timer.interval();
 
-        loop {
+        let presence_timer = Timer::new(HEARTBEAT_INTERVAL, {
+            let watcher = Arc::clone(&self);
+            move || {
+                let results = [
+                    watcher.tick(),
+                    watcher.update_schedule(),
+                    watcher.update_presence(),
+                ];
+                for err in results.into_iter().filter_map(Result::err) {
+                    error!("heartbeat error: {}", err);
+                }
+            }
+        });
+        presence_timer.interval();
+
+        while !is_shutdown() {
+            if let Ok(message) = WATCHER_MESSAGE_CHANNEL.lock().1.try_recv() {
+                self.receive(message);
+            }
+            if let Ok(event) = WATCHER_SESSION_CHANNEL.lock().1.try_recv() {
+                if let Err(err) = self.session_changed(event) {
+                    error!("session change error: {}", err);
+                }
+            }
+            if WATCHER_TOGGLE_CHANNEL.lock().1.try_recv().is_ok() {
+                if let Err(err) = self.toggle() {
+                    error!("toggle error: {}", err);
+                }
+            }
             if let Ok(event) = WATCHER_EVENT_CHANNEL.lock().1.try_recv() {
                 if !*self.running.read() {
                     continue;
                 }
-                self.handle(event);
+                if let Err(err) = self.handle(event) {
+                    error!("handle event error: {}", err);
+                }
             }
             thread::sleep(Duration::from_millis(1000 / 60));
         }
     }
 
-    fn handle(self: &Arc<Self>, event: WatcherEvent) {
+    /// Notices sleep and clock changes nothing else reported, sessions must not span them.
+    fn tick(&self) -> crate::Result<()> {
+        let jump = self.heartbeat.lock().tick(
+            Utc::now().timestamp_millis(),
+            clock::monotonic(),
+            HEARTBEAT_INTERVAL.as_millis() as i64,
+        );
+        if let Some(jump) = jump {
+            info!("clock jump: {:?}", jump);
+            let reason = match jump.kind {
+                JumpKind::Sleep => Reason::Sleep,
+                JumpKind::Adjusted => Reason::ClockChanged,
+            };
+            self.split(jump.from, reason)?;
+            self.emit(EVENT_CLOCK_JUMPED, jump)?;
+        }
+        Ok(())
+    }
+
+    /// Ends suspensions for a duration and pauses or resumes with the schedule.
+    fn update_schedule(&self) -> crate::Result<()> {
+        let now = Utc::now();
+        let expired = self
+            .suspended_until
+            .lock()
+            .is_some_and(|until| until <= now.timestamp_millis());
+        if expired {
+            self.set_resumed(true, None)?;
+            self.schedule_changed(ScheduleCause::Timer, now.timestamp_millis())?;
+        }
+        let scheduled = self
+            .schedule
+            .allows(now.with_timezone(&Local).naive_local());
+        if scheduled != *self.scheduled.read() {
+            *self.scheduled.write() = scheduled;
+            self.schedule_changed(ScheduleCause::Schedule, now.timestamp_millis())?;
+        }
+        Ok(())
+    }
+
+    fn schedule_changed(&self, cause: ScheduleCause, time: i64) -> crate::Result<()> {
+        let running = self.update_running()?;
+        self.emit_state()?;
+        info!("{:?} changed, running: {}", cause, running);
+        self.emit(
+            EVENT_SCHEDULE_CHANGED,
+            ScheduleChange {
+                cause,
+                running,
+                time,
+            },
+        )
+    }
+
+    fn update_presence(&self) -> crate::Result<()> {
+        let now = Utc::now().timestamp_millis();
+        let mut presence = self.presence.lock();
+        let idle = presence.idle_time(now, presence::os_idle_time());
+        if let Some(status) = presence.update(now, idle) {
+            drop(presence);
+            debug!("presence: {:?}", status.state);
+            self.emit(EVENT_PRESENCE_CHANGED, status)?;
+        }
+        Ok(())
+    }
+
+    /// Locking the screen or sleeping ends the foreground sessions, audio keeps playing.
+    fn session_changed(&self, event: SessionEvent) -> crate::Result<()> {
+        debug!("session: {:?}", event);
+        let now = Utc::now().timestamp_millis();
+        let mut presence = self.presence.lock();
+        let status = match event {
+            SessionEvent::Lock => presence.set_locked(now, true),
+            SessionEvent::Unlock => presence.set_locked(now, false),
+            SessionEvent::Sleep => presence.set_sleeping(now, true),
+            SessionEvent::Wake => presence.set_sleeping(now, false),
+        };
+        let locked = presence.is_locked();
+        drop(presence);
+        if let Some(status) = status {
+            self.emit(EVENT_PRESENCE_CHANGED, status)?;
+        }
+        if event == SessionEvent::Sleep {
+            self.remove_windows(Reason::Sleep)?;
+        } else if locked {
+            self.remove_windows(Reason::Locked)?;
+        }
+        Ok(())
+    }
+
+    /// Attribution of an event and how long its program stays active without input,
+    /// `None` for ignored programs. Events without a window, such as audio, only get a category.
+    fn attribute(&self, event: &WatcherEvent) -> Option<(Attribution, Duration)> {
+        let pid = event.title.as_ref().and(event.pid);
+        let activity = pid.and_then(|pid| {
+            self.shells
+                .activity(pid)
+                .or_else(|| self.browsers.activity(pid, &event.path))
+        });
+        let domain = match &activity {
+            Some(Activity::Browser { domain, .. }) => Some(domain.as_str()),
+            _ => None,
+        };
+        let classification = self.rules.classify(&Subject {
+            path: &event.path,
+            title: event.title.as_deref(),
+            domain,
+        });
+        if classification.ignored {
+            return None;
+        }
+        let attribution = Attribution {
+            project: classification.project,
+            repository: pid.and_then(|pid| self.repositories.resolve(pid, &event.path)),
+            activity,
+            category: classification
+                .category
+                .map(|name| self.config.category.category(&name)),
+        };
+        let timeout = classification
+            .timeout
+            .or(self.config.idle_timeout)
+            .map_or(DEFAULT_TIMEOUT, Duration::from_secs);
+        Some((attribution, timeout))
+    }
+
+    fn receive(&self, message: Message) {
+        match message {
+            Message::CommandStart { pid, cwd, command } => {
+                self.shells.update(pid, cwd, Some(command))
+            }
+            Message::CommandEnd { pid, cwd, .. } => self.shells.update(pid, cwd, None),
+            Message::Tab { pid, url, title } => self.browsers.update(pid, url, title),
+        }
+        self.refresh();
+    }
+
+    /// Re-attributes the pooled programs, e.g. after a shell started a command. Programs
+    /// still in the same session, such as a browser on another page of the same domain,
+    /// are updated in place.
+    fn refresh(&self) {
         let mut pool = self.pool.lock();
-        let index = pool.iter().position(|p| p.path == event.path);
+        for program in pool
+            .iter_mut()
+            .filter(|p| p.title.is_some() && !p.is_manual())
+        {
+            let event = WatcherEvent {
+                path: program.path.clone(),
+                is_audio: false,
+                active: true,
+                input: program.activation == Activation::Input,
+                source: program.source.clone(),
+                title: program.title.clone(),
+                pid: program.pid,
+            };
+            match self.attribute(&event) {
+                Some((attribution, _)) if attribution.same_session(&program.attribution) => {
+                    program.attribution = attribution;
+                }
+                _ => {
+                    let _ = WATCHER_EVENT_CHANNEL.lock().0.send(event);
+                }
+            }
+        }
+    }
+
+    fn handle(self: &Arc<Self>, event: WatcherEvent) -> crate::Result<()> {
+        // input on the lock screen
+        if event.active && !event.is_audio && self.presence.lock().is_locked() {
+            return Ok(());
+        }
+        let (attribution, timeout) = match self.attribute(&event) {
+            Some(attributed) => attributed,
+            None => {
+                // an ignored window still takes the focus from the tracked ones
+                if event.active && !event.is_audio {
+                    self.remove_windows(Reason::Switched)?;
+                }
+                return Ok(());
+            }
+        };
+        let mut pool = self.pool.lock();
+        let mut index = pool.iter().position(|p| p.path == event.path);
         if !event.active {
             if let Some(index) = index {
-                if !event.is_audio && !pool[index].is_audio {
+                let program = &mut pool[index];
+                let id = program.id;
+                // a program only playing audio, without a window, ends with `audio_stop`
+                let audio_only = event.is_audio && program.title.is_none();
+                if audio_only || (!event.is_audio && !program.is_audio) {
                     drop(pool);
-                    self.remove(index);
+                    self.remove(id, Reason::Inactive)?;
                 } else {
-                    let program = &mut pool[index];
                     if event.is_audio {
                         program.is_audio = false;
                     }
                     drop(pool);
-                    self.reset_timer(index);
+                    self.reset_timer(id);
                 }
             }
-            return;
+            return Ok(());
         }
+        let mut is_audio = event.is_audio;
+        // the new session starts even if ending the previous one could not be emitted
+        let mut result = Ok(());
+        if let Some(i) = index {
+            // switching project or repository inside the same program starts a new session
+            if event.title.is_some() && !attribution.same_session(&pool[i].attribution) {
+                is_audio |= pool[i].is_audio;
+                let id = pool[i].id;
+                drop(pool);
+                result = self.remove(id, Reason::Switched);
+                pool = self.pool.lock();
+                index = None;
+            }
+        }
+        let activation = event.activation();
         if let Some(index) = index {
             let program = &mut pool[index];
+            let id = program.id;
+            program.activation = activation;
             if event.is_audio {
                 program.is_audio = true;
             }
+            if event.title.is_some() {
+                program.title = event.title;
+                program.pid = event.pid;
+                // e.g. the page of a tab on the same domain
+                program.attribution = attribution;
+            }
             drop(pool);
-            self.reset_timer(index);
+            self.reset_timer(id);
+            result
         } else {
-            let mut list = vec![];
-            for (i, _) in pool.iter().enumerate() {
-                if !pool[i].is_audio {
-                    list.push(i);
-                }
-            }
             drop(pool);
-            list.reverse();
-            for i in list {
-                self.remove(i)
-            }
-            let timer = Timer::new(Duration::from_secs(60), {
+            result = result.and(self.remove_windows(Reason::Switched));
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            let timer = Timer::new(timeout, {
                 let watcher = Arc::clone(&self);
-                let path = event.path.clone();
                 move || {
                     let pool = watcher.pool.lock();
-                    let index = pool.iter().position(|p| p.path == path);
-                    if let Some(index) = index {
-                        if !pool[index].is_audio {
-                            drop(pool);
-                            watcher.remove(index);
+                    // restarted once the manual activity stops
+                    let timed_out = !is_idle_paused(&pool)
+                        && pool.iter().any(|p| p.id == id && p.needs_input());
+                    drop(pool);
+                    if timed_out {
+                        if let Err(err) = watcher.remove(id, Reason::Timeout) {
+                            error!("timeout error: {}", err);
                         }
                     }
                 }
             });
             timer.timeout();
-            self.add(Program {
+            result.and(self.add(Program {
+                id,
                 path: event.path,
-                is_audio: event.is_audio,
+                is_audio,
                 timer,
-            })
+                attribution,
+                active_since: Utc::now().timestamp_millis(),
+                activation,
+                title: event.title,
+                pid: event.pid,
+                source: event.source,
+            }))
         }
     }
 
-    fn remove(&self, index: usize) {
+    /// Ends the sessions of every program not playing audio, except manual activities.
+    fn remove_windows(&self, reason: Reason) -> crate::Result<()> {
         let mut pool = self.pool.lock();
-        let path = pool[index].path.clone();
-        pool.remove(index);
-        self.app
-            .emit(
-                EVENT_STATUS_CHANGED,
-                WindowStatus {
-                    path: path.clone(),
-                    active: false,
-                    time: Utc::now().timestamp_millis(),
-                },
-            )
-            .unwrap();
-        debug!("remove program: {}", path);
-    }
-
-    fn add(&self, program: Program) {
+        let (ended, kept): (Vec<Program>, Vec<Program>) =
+            pool.drain(..).partition(Program::needs_input);
+        *pool = kept;
+        drop(pool);
+        self.end(ended, reason)
+    }
+
+    fn remove_all(&self, reason: Reason) -> crate::Result<()> {
+        let programs: Vec<Program> = self.pool.lock().drain(..).collect();
+        self.end(programs, reason)
+    }
+
+    /// Ends the session of the program with `id`, unless it already ended.
+    fn remove(&self, id: u64, reason: Reason) -> crate::Result<()> {
         let mut pool = self.pool.lock();
-        let path = program.path.clone();
-        pool.push(program);
-        self.app
-            .emit(
-                EVENT_STATUS_CHANGED,
-                WindowStatus {
-                    path: path.clone(),
-                    active: true,
-                    time: Utc::now().timestamp_millis(),
-                },
-            )
-            .unwrap();
-        debug!("add program: {}", path);
-    }
-
-    fn reset_timer(&self, index: usize) {
-        let pool = self.pool.lock();
-        let program = &pool[index];
-        program.timer.reset();
+        let index = match pool.iter().position(|p| p.id == id) {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        let program = pool.remove(index);
+        drop(pool);
+        self.end(vec![program], reason)
     }
 
-    pub fn suspend(&self) {
-        *self.running.write() = false;
+    /// Records the sessions of `programs` removed from the pool, then emits their statuses.
+    /// Every session is recorded even if emitting fails.
+    fn end(&self, programs: Vec<Program>, reason: Reason) -> crate::Result<()> {
+        let time = Utc::now().timestamp_millis();
+        for program in &programs {
+            self.store.record(program.session(time, Some(reason)));
+            debug!("remove program: {}", program.path);
+        }
+        self.emit_statuses(programs.iter().map(|p| p.ended(time, reason)))
+    }
+
+    /// Ends every session at `end`, before the clock jumped. After sleeping windows wait for
+    /// the next input, everything else continues in a new session.
+    fn split(&self, end: i64, reason: Reason) -> crate::Result<()> {
+        let now = Utc::now().timestamp_millis();
         let mut pool = self.pool.lock();
-        pool.clear();
-        let _ = WATCHER_STATUS_CHANNEL
+        let mut statuses = vec![];
+        for program in pool.iter_mut() {
+            self.store.record(program.session(end, Some(reason)));
+            statuses.push(program.ended(end, reason));
+            if reason != Reason::Sleep || !program.needs_input() {
+                program.active_since = now;
+                statuses.push(program.started());
+            }
+        }
+        pool.retain(|p| reason != Reason::Sleep || !p.needs_input());
+        drop(pool);
+        self.emit_statuses(statuses)
+    }
+
+    /// Emits every status, logging failures, and returns the first error.
+    fn emit_statuses(&self, statuses: impl IntoIterator<Item = WindowStatus>) -> crate::Result<()> {
+        let mut result = Ok(());
+        for status in statuses {
+            if let Err(err) = self.emit_status(status) {
+                error!("emit status error: {}", err);
+                result = result.and(Err(err));
+            }
+        }
+        result
+    }
+
+    fn emit_status(&self, status: WindowStatus) -> crate::Result<()> {
+        // dropped receivers unsubscribe
+        self.subscribers
             .lock()
-            .0
-            .send(WatcherStatus { running: false });
+            .retain(|subscriber| subscriber.send(status.clone()).is_ok());
+        self.emit(EVENT_STATUS_CHANGED, status)
     }
 
-    pub fn resume(&self) {
-        *self.running.write() = true;
+    fn add(&self, program: Program) -> crate::Result<()> {
+        let status = program.started();
+        debug!("add program: {}", program.path);
+        self.pool.lock().push(program);
+        self.emit_status(status)
+    }
+
+    fn reset_timer(&self, id: u64) {
+        let pool = self.pool.lock();
+        if let Some(program) = pool.iter().find(|p| p.id == id) {
+            program.timer.reset();
+        }
+    }
+
+    /// Starts or stops tracking after being resumed, suspended or scheduled, returning
+    /// whether it runs.
+    fn update_running(&self) -> crate::Result<bool> {
+        let running = *self.resumed.read() && *self.scheduled.read();
+        let mut current = self.running.write();
+        if *current == running {
+            return Ok(running);
+        }
+        *current = running;
+        drop(current);
         let _ = WATCHER_STATUS_CHANNEL
             .lock()
             .0
-            .send(WatcherStatus { running: true });
+            .send(WatcherStatus { running });
+        if !running {
+            self.remove_all(Reason::Suspended)?;
+        }
+        Ok(running)
+    }
+
+    /// Resumes or suspends, until `until` if set, and saves it for the next start.
+    fn set_resumed(&self, resumed: bool, until: Option<i64>) -> crate::Result<()> {
+        *self.resumed.write() = resumed;
+        *self.suspended_until.lock() = until;
+        if let Some(file) = &self.state_file {
+            let state = SavedState {
+                running: resumed,
+                suspended_until: until,
+            };
+            if let Err(err) = file.save(&state) {
+                error!("save state error: {}", err);
+            }
+        }
+        self.update_running()?;
+        self.emit_state()
+    }
+
+    pub fn state(&self) -> WatcherState {
+        let pool = self.pool.lock();
+        let idle_paused = is_idle_paused(&pool);
+        WatcherState {
+            running: *self.running.read(),
+            resumed: *self.resumed.read(),
+            suspended_until: *self.suspended_until.lock(),
+            scheduled: *self.scheduled.read(),
+            backends: self.backends.statuses(),
+            pool: pool.iter().map(|p| p.status(idle_paused)).collect(),
+        }
+    }
+
+    /// Emits `event` to the frontends, numbered and kept for replaying.
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) -> crate::Result<()> {
+        // numbered in the order they are emitted. Rust listeners run inside `app.emit` and
+        // may call back into the watcher, so only the reentrant order lock is held there.
+        let _order = self.emit_order.lock();
+        let payload = self.journal.lock().push(event, payload)?;
+        self.app.emit(event, payload)?;
+        Ok(())
+    }
+
+    /// Events emitted after `seq`, or a snapshot when they are no longer kept.
+    pub fn events_since(&self, seq: u64) -> Replay {
+        let journal = self.journal.lock();
+        if let Some(events) = journal.since(seq) {
+            return Replay::Events { events };
+        }
+        // events after the snapshot's seq may show up in it as well
+        let seq = journal.seq();
+        drop(journal);
+        Replay::Snapshot {
+            seq,
+            state: self.state(),
+        }
+    }
+
+    fn emit_state(&self) -> crate::Result<()> {
+        self.emit(EVENT_STATE_CHANGED, self.state())
+    }
+
+    pub fn suspend(&self) -> crate::Result<()> {
+        self.set_resumed(false, None)
+    }
+
+    /// Suspends until `time` in milliseconds, then resumes.
+    pub fn suspend_until(&self, time: i64) -> crate::Result<()> {
+        self.set_resumed(false, Some(time))
+    }
+
+    pub fn suspend_for(&self, duration: Duration) -> crate::Result<()> {
+        let until = i64::try_from(duration.as_millis())
+            .ok()
+            .and_then(|millis| Utc::now().timestamp_millis().checked_add(millis))
+            .ok_or_else(|| anyhow!("suspension of {:?} is out of range", duration))?;
+        self.suspend_until(until)
+    }
+
+    /// Resumes tracking, outside the schedule only once it starts.
+    pub fn resume(&self) -> crate::Result<()> {
+        self.set_resumed(true, None)
+    }
+
+    /// Suspends or resumes from the hotkey.
+    fn toggle(&self) -> crate::Result<()> {
+        let resumed = !*self.resumed.read();
+        self.set_resumed(resumed, None)?;
+        let running = *self.running.read();
+        info!("toggled, resumed: {}, running: {}", resumed, running);
+        self.emit(
+            EVENT_TOGGLED,
+            Toggled {
+                resumed,
+                running,
+                time: Utc::now().timestamp_millis(),
+            },
+        )
+    }
+
+    /// Logs offline work, like a meeting, as `label` until stopped. It runs alongside the
+    /// programs in use and pauses their idle timeouts, starting `label` again while it runs
+    /// changes nothing.
+    pub fn start_manual_activity(&self, label: String) -> crate::Result<()> {
+        if !*self.running.read() {
+            return Err(anyhow!("tracking is suspended").into());
+        }
+        let path = format!("{}:{}", MANUAL_SOURCE, label);
+        if self
+            .pool
+            .lock()
+            .iter()
+            .any(|p| p.is_manual() && p.path == path)
+        {
+            return Ok(());
+        }
+        self.stop_manual_activity()?;
+        let event = WatcherEvent {
+            path,
+            is_audio: false,
+            active: true,
+            input: false,
+            title: Some(label),
+            pid: None,
+            source: Some(MANUAL_SOURCE.to_string()),
+        };
+        // project and category rules apply to the label as the title
+        let attribution = self
+            .attribute(&event)
+            .map(|(attribution, _)| attribution)
+            .unwrap_or_default();
+        self.add(Program {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            path: event.path,
+            is_audio: false,
+            // never started
+            timer: Timer::new(Duration::ZERO, || {}),
+            attribution,
+            active_since: Utc::now().timestamp_millis(),
+            activation: Activation::Manual,
+            title: event.title,
+            pid: None,
+            source: event.source,
+        })
+    }
+
+    /// Ends the running manual activity, if any, and restarts the idle timeouts.
+    pub fn stop_manual_activity(&self) -> crate::Result<()> {
+        let pool = self.pool.lock();
+        let id = pool.iter().find(|p| p.is_manual()).map(|p| p.id);
+        drop(pool);
+        let id = match id {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let result = self.remove(id, Reason::Stopped);
+        for program in self.pool.lock().iter() {
+            program.timer.reset();
+        }
+        result
+    }
+
+    /// Ends the open sessions, flushes the store and stops the threads once the app exits. The
+    /// input hook keeps blocking in the OS until the process ends, ignoring further input.
+    pub fn shutdown(&self) {
+        if SHUTDOWN.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        info!("shutdown");
+        if let Err(err) = self.remove_all(Reason::Shutdown) {
+            error!("end sessions error: {}", err);
+        }
+        if let Err(err) = self.store.flush() {
+            error!("flush store error: {}", err);
+        }
+        #[cfg(target_os = "windows")]
+        windows::App::stop();
+        #[cfg(target_os = "linux")]
+        linux::logind::stop();
+    }
+
+    /// Runs `source` on its own thread, its events are handled once the watcher runs. Unlike
+    /// the built-in backends it is not restarted, `run` consumes it.
+    pub fn add_source(&self, source: Box<dyn ActivitySource>) {
+        let id = source.id();
+        let sender = ActivitySender::new(&id);
+        self.backends.spawn(&id, move || source.run(sender));
+    }
+
+    /// Status events from now on, until the receiver is dropped.
+    pub fn subscribe(&self) -> Receiver<WindowStatus> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        self.subscribers.lock().push(tx);
+        rx
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
     }
 
     pub fn is_active(&self, path: String) -> bool {
         let pool = self.pool.lock();
         pool.iter().find(|p| p.path == path).is_some()
     }
+
+    pub fn presence(&self) -> PresenceStatus {
+        self.presence.lock().status()
+    }
+
+    pub fn shell_integration(&self, name: &str) -> crate::Result<String> {
+        shell::integration(name, &self.config.socket.path())
+    }
+
+    pub fn aggregates(&self, group_by: GroupBy, since: Option<i64>) -> Vec<Aggregate> {
+        let mut sessions = self.store.sessions();
+        let now = Utc::now().timestamp_millis();
+        sessions.extend(self.pool.lock().iter().map(|p| p.session(now, None)));
+        aggregate(&sessions, group_by, since)
+    }
+
+    /// Every rule evaluated for a window of `path` titled `title`. Browsers are matched
+    /// against the domain of their active tab, if they are being tracked.
+    pub fn explain(&self, path: String, title: Option<String>) -> Explanation {
+        let pool = self.pool.lock();
+        let domain =
+            pool.iter()
+                .find(|p| p.path == path)
+                .and_then(|p| match &p.attribution.activity {
+                    Some(Activity::Browser { domain, .. }) => Some(domain.clone()),
+                    _ => None,
+                });
+        drop(pool);
+        self.rules.explain(&Subject {
+            path: &path,
+            title: title.as_deref(),
+            domain: domain.as_deref(),
+        })
+    }
+
+    pub fn productivity(&self, since: Option<i64>) -> Productivity {
+        let mut sessions = self.store.sessions();
+        let now = Utc::now().timestamp_millis();
+        sessions.extend(self.pool.lock().iter().map(|p| p.session(now, None)));
+        productivity(&sessions, since)
+    }
+}
+
+/// Whether a manual activity runs, the other programs don't time out meanwhile.
+fn is_idle_paused(pool: &[Program]) -> bool {
+    pool.iter().any(Program::is_manual)
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
+    use crate::store::MemoryStore;
+    use tauri::test::{mock_app, MockRuntime};
 
     // #[test]
     // fn test_watcher_run() {
     //     let watcher = Watcher::new();
     //     watcher.run()
     // }
+
+    fn watcher() -> Arc<Watcher<MockRuntime>> {
+        let config = Config {
+            idle_timeout: Some(1),
+            start_running: true,
+            ..Default::default()
+        };
+        let app = mock_app();
+        Watcher::new(
+            app.handle().clone(),
+            &config,
+            Box::new(MemoryStore::default()),
+        )
+        .unwrap()
+    }
+
+    fn focus(path: &str) -> WatcherEvent {
+        WatcherEvent {
+            path: path.into(),
+            is_audio: false,
+            active: true,
+            input: true,
+            title: None,
+            pid: None,
+            source: None,
+        }
+    }
+
+    fn paths(watcher: &Watcher<MockRuntime>) -> Vec<String> {
+        watcher.pool.lock().iter().map(|p| p.path.clone()).collect()
+    }
+
+    #[test]
+    fn test_audio_stop() {
+        let watcher = watcher();
+        let statuses = watcher.subscribe();
+        let audio = |active| WatcherEvent {
+            path: "/usr/bin/spotify".into(),
+            is_audio: true,
+            active,
+            input: false,
+            title: None,
+            pid: None,
+            source: None,
+        };
+        watcher.handle(audio(true)).unwrap();
+        watcher.handle(audio(false)).unwrap();
+        assert!(paths(&watcher).is_empty());
+        let reasons: Vec<_> = statuses.try_iter().map(|s| (s.active, s.reason)).collect();
+        assert_eq!(
+            reasons,
+            [
+                (true, StatusReason::AudioStart),
+                (false, StatusReason::AudioStop),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_manual_activity() {
+        let watcher = watcher();
+        watcher.start_manual_activity("meeting".into()).unwrap();
+        let id = watcher.pool.lock()[0].id;
+        // the same label keeps the session
+        watcher.start_manual_activity("meeting".into()).unwrap();
+        assert_eq!(paths(&watcher), ["manual:meeting"]);
+        assert_eq!(watcher.pool.lock()[0].id, id);
+        // another label replaces it
+        watcher.start_manual_activity("review".into()).unwrap();
+        assert_eq!(paths(&watcher), ["manual:review"]);
+        watcher.stop_manual_activity().unwrap();
+        assert!(paths(&watcher).is_empty());
+        let reasons: Vec<_> = watcher
+            .store
+            .sessions()
+            .iter()
+            .map(|s| (s.path.clone(), s.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                ("manual:meeting".to_string(), Some(Reason::Stopped)),
+                ("manual:review".to_string(), Some(Reason::Stopped)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_manual_activity_pauses_idle_timeout() {
+        let watcher = watcher();
+        watcher.handle(focus("/usr/bin/code")).unwrap();
+        watcher.start_manual_activity("meeting".into()).unwrap();
+        thread::sleep(Duration::from_millis(1500));
+        assert_eq!(paths(&watcher), ["/usr/bin/code", "manual:meeting"]);
+        assert!(watcher.state().pool[0].timeout_remaining.is_none());
+
+        // the timeout starts over once stopped
+        watcher.stop_manual_activity().unwrap();
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(paths(&watcher), ["/usr/bin/code"]);
+        thread::sleep(Duration::from_millis(1000));
+        assert!(paths(&watcher).is_empty());
+    }
 }
@@ -5,20 +5,20 @@ use std::collections::HashMap;
 
 use std::hash::{Hash, Hasher};
 use std::io::Cursor;
+use std::mem;
 use std::mem::MaybeUninit;
 use std::os::windows::prelude::OsStrExt;
 use std::path::{Path, PathBuf};
 use std::ptr::addr_of_mut;
-use std::sync::Mutex;
-use std::{iter, mem};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, ensure, Result};
 use image::RgbaImage;
 use once_cell::sync::Lazy;
+use parking_lot::Mutex;
 use windows::Win32::Foundation::{HMODULE, HWND};
 use windows::Win32::Graphics::Gdi::{
     DeleteObject, GetDC, GetDIBits, GetObjectW, ReleaseDC, BITMAP, BITMAPINFOHEADER, BI_RGB,
-    DIB_RGB_COLORS, HDC,
+    DIB_RGB_COLORS, HBITMAP, HDC,
 };
 use windows::Win32::System::LibraryLoader::GetModuleHandleW;
 use windows::Win32::UI::Shell::ExtractAssociatedIconW;
@@ -37,25 +37,27 @@ pub struct IconCache {
 impl IconCache {
     pub fn get_png(&mut self, executable: PathBuf) -> Result<&Vec<u8>> {
         match self.executables.entry(executable) {
-            Entry::Occupied(e) => {
-                // Guaranteed to exist because we never clear the cache.
-                Ok(self.icons.get(e.get()).unwrap())
-            }
+            Entry::Occupied(e) => self
+                .icons
+                .get(e.get())
+                .ok_or_else(|| anyhow!("icon missing from the cache")),
             Entry::Vacant(e) => {
                 let pixels = unsafe {
                     let hinst = GetModuleHandleW(None)?;
                     icon_for_executable(e.key(), hinst)?
                 };
                 let pixel_hash = pixels.hash();
+                let icon = match self.icons.entry(pixel_hash) {
+                    Entry::Occupied(icon) => icon.into_mut(),
+                    Entry::Vacant(icon) => {
+                        let mut c = Cursor::new(Vec::new());
+                        pixels
+                            .to_image()
+                            .write_to(&mut c, image::ImageFormat::Png)?;
+                        icon.insert(c.into_inner())
+                    }
+                };
                 e.insert(pixel_hash);
-                let icon = self.icons.entry(pixel_hash).or_insert_with(|| {
-                    let mut c = Cursor::new(Vec::new());
-                    pixels
-                        .to_image()
-                        .write_to(&mut c, image::ImageFormat::Png)
-                        .unwrap();
-                    c.into_inner()
-                });
                 Ok(icon)
             }
         }
@@ -85,14 +87,13 @@ impl PixelData {
 }
 
 unsafe fn icon_for_executable(executable: &Path, hinst: HMODULE) -> Result<PixelData> {
-    let mut icon_path_u16: [u16; 128] = executable
-        .as_os_str()
-        .encode_wide()
-        .chain(iter::repeat(0))
-        .take(128)
-        .collect::<Vec<u16>>()
-        .try_into()
-        .unwrap();
+    let mut icon_path_u16 = [0u16; 128];
+    for (dst, src) in icon_path_u16
+        .iter_mut()
+        .zip(executable.as_os_str().encode_wide())
+    {
+        *dst = src;
+    }
     let mut icon_idx = 0;
     let icon = ExtractAssociatedIconW(
         hinst,
@@ -106,37 +107,40 @@ unsafe fn icon_for_executable(executable: &Path, hinst: HMODULE) -> Result<Pixel
     let pixels = icon_to_pixels(icon);
     DestroyIcon(icon)?;
 
-    Ok(pixels)
+    pixels
 }
 
 /// adapted from https://users.rust-lang.org/t/how-to-convert-hicon-to-png/90975/10
-unsafe fn icon_to_pixels(icon: HICON) -> PixelData {
-    let bitmap_size_i32 = i32::try_from(mem::size_of::<BITMAP>()).unwrap();
-    let biheader_size_u32 = u32::try_from(mem::size_of::<BITMAPINFOHEADER>()).unwrap();
-
+unsafe fn icon_to_pixels(icon: HICON) -> Result<PixelData> {
     let mut info = MaybeUninit::uninit();
-    GetIconInfo(icon, info.as_mut_ptr()).unwrap();
+    GetIconInfo(icon, info.as_mut_ptr())?;
     let info = info.assume_init_ref();
-    DeleteObject(info.hbmMask).unwrap();
+    let _ = DeleteObject(info.hbmMask);
+    let pixels = bitmap_to_pixels(info.hbmColor);
+    let _ = DeleteObject(info.hbmColor);
+    pixels
+}
+
+unsafe fn bitmap_to_pixels(color: HBITMAP) -> Result<PixelData> {
+    let bitmap_size_i32 = i32::try_from(mem::size_of::<BITMAP>())?;
+    let biheader_size_u32 = u32::try_from(mem::size_of::<BITMAPINFOHEADER>())?;
 
     let mut bitmap: MaybeUninit<BITMAP> = MaybeUninit::uninit();
-    let result = GetObjectW(
-        info.hbmColor,
-        bitmap_size_i32,
-        Some(bitmap.as_mut_ptr().cast()),
-    );
-    assert!(result == bitmap_size_i32);
+    let result = GetObjectW(color, bitmap_size_i32, Some(bitmap.as_mut_ptr().cast()));
+    ensure!(result == bitmap_size_i32, "unable to get icon bitmap");
     let bitmap = bitmap.assume_init_ref();
 
-    let width_u32 = u32::try_from(bitmap.bmWidth).unwrap();
-    let height_u32 = u32::try_from(bitmap.bmHeight).unwrap();
-    let width_usize = usize::try_from(bitmap.bmWidth).unwrap();
-    let height_usize = usize::try_from(bitmap.bmHeight).unwrap();
-    let buf_size = width_usize.checked_mul(height_usize).unwrap();
+    let width_u32 = u32::try_from(bitmap.bmWidth)?;
+    let height_u32 = u32::try_from(bitmap.bmHeight)?;
+    let width_usize = usize::try_from(bitmap.bmWidth)?;
+    let height_usize = usize::try_from(bitmap.bmHeight)?;
+    let buf_size = width_usize
+        .checked_mul(height_usize)
+        .ok_or_else(|| anyhow!("icon too large"))?;
     let mut buf: Vec<u32> = Vec::with_capacity(buf_size);
 
     let dc = GetDC(HWND(0));
-    assert!(dc != HDC(0));
+    ensure!(dc != HDC(0), "unable to get device context");
 
     let mut bitmap_info = BITMAPINFOHEADER {
         biSize: biheader_size_u32,
@@ -153,23 +157,20 @@ unsafe fn icon_to_pixels(icon: HICON) -> PixelData {
     };
     let result = GetDIBits(
         dc,
-        info.hbmColor,
+        color,
         0,
         height_u32,
         Some(buf.as_mut_ptr().cast()),
         addr_of_mut!(bitmap_info).cast(),
         DIB_RGB_COLORS,
     );
-    assert!(result == bitmap.bmHeight);
+    let _ = ReleaseDC(HWND(0), dc);
+    ensure!(result == bitmap.bmHeight, "unable to read icon bits");
     buf.set_len(buf.capacity());
 
-    let result = ReleaseDC(HWND(0), dc);
-    assert!(result == 1);
-    DeleteObject(info.hbmColor).unwrap();
-
-    PixelData {
+    Ok(PixelData {
         bgra: buf,
         width: width_u32,
         height: height_u32,
-    }
-}
\ No newline at end of file
+    })
+}
@@ -1,36 +1,53 @@
-use std::{collections::HashSet, os::windows::ffi::OsStrExt, path::Path, thread, time::Duration};
+use std::{
+    collections::HashSet,
+    os::windows::ffi::OsStrExt,
+    path::Path,
+    sync::atomic::{AtomicU32, Ordering},
+    thread,
+    time::Duration,
+};
 
 use anyhow::{anyhow, bail};
 use nodio_win32::{AudioSessionEvent, SessionState, Win32Context};
+use once_cell::sync::Lazy;
 use windows::{
     core::{w, PCWSTR, PWSTR},
     Win32::{
-        Foundation::{GetLastError, BOOL, HWND, LPARAM, MAX_PATH, POINT},
+        Foundation::{
+            CloseHandle, GetLastError, BOOL, FILETIME, HANDLE, HWND, LPARAM, MAX_PATH, POINT,
+            WPARAM,
+        },
         Storage::FileSystem::{GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW},
+        System::SystemInformation::GetTickCount,
         System::Threading::{
-            OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_INFORMATION,
-            PROCESS_VM_READ,
+            GetCurrentThreadId, GetProcessTimes, OpenProcess, QueryFullProcessImageNameW,
+            PROCESS_NAME_WIN32, PROCESS_QUERY_INFORMATION, PROCESS_VM_READ,
         },
         UI::{
             Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK},
+            Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO},
             WindowsAndMessaging::{
-                DispatchMessageW, EnumWindows, GetCursorPos, GetForegroundWindow, GetMessageW,
-                GetWindow, GetWindowTextW, GetWindowThreadProcessId, IsWindowVisible,
-                TranslateMessage, WindowFromPoint, EVENT_SYSTEM_FOREGROUND, GWL_EXSTYLE, GW_OWNER,
-                MSG, WINEVENT_OUTOFCONTEXT, WS_EX_TOPMOST,
+                DispatchMessageW, EnumWindows, GetAncestor, GetCursorPos, GetForegroundWindow,
+                GetMessageW, GetWindow, GetWindowTextW, GetWindowThreadProcessId, IsWindowVisible,
+                PostThreadMessageW, TranslateMessage, WindowFromPoint, EVENT_SYSTEM_FOREGROUND,
+                GA_ROOT, GWL_EXSTYLE, GW_OWNER, MSG, WINEVENT_OUTOFCONTEXT, WM_QUIT, WS_EX_TOPMOST,
             },
         },
     },
 };
 
 use crate::{
-    shared::Program,
-    watcher::{WatcherEvent, WATCHER_EVENT_CHANNEL, WATCHER_STATUS_CHANNEL},
+    shared::{PathCache, Program, WindowInfo},
+    watcher::{self, WatcherEvent, WATCHER_EVENT_CHANNEL, WATCHER_STATUS_CHANNEL},
     Result,
 };
 
 mod icons;
 
+static PATHS: Lazy<PathCache> = Lazy::new(PathCache::default);
+/// Thread running the message loop of the foreground hook, `0` before it starts.
+static EVENT_LOOP_THREAD: AtomicU32 = AtomicU32::new(0);
+
 pub fn get_program_list() -> Result<Vec<Program>> {
     let processes = get_foreground_processes();
     let mut programs = Vec::new();
@@ -38,7 +55,10 @@ pub fn get_program_list() -> Result<Vec<Program>> {
     for pid in processes {
         if let Some(path) = get_program_path(pid) {
             let program = get_program_by_path(path)?;
-            let has = programs.iter().position(|p: &Program| p.path == program.path).is_some();
+            let has = programs
+                .iter()
+                .position(|p: &Program| p.path == program.path)
+                .is_some();
             if !has {
                 programs.push(program);
             }
@@ -48,10 +68,29 @@ pub fn get_program_list() -> Result<Vec<Program>> {
     Ok(programs)
 }
 
+/// Milliseconds since the last input in the session.
+pub fn get_idle_time() -> Option<i64> {
+    let mut info = LASTINPUTINFO {
+        cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    unsafe {
+        if !GetLastInputInfo(&mut info).as_bool() {
+            return None;
+        }
+        // both wrap around after 49.7 days
+        Some(GetTickCount().wrapping_sub(info.dwTime) as i64)
+    }
+}
+
 pub fn get_program_by_path(path: String) -> Result<Program> {
     let file_path = Path::new(&path);
 
-    let stem = file_path.file_stem().unwrap().to_str().unwrap().to_string();
+    let stem = file_path
+        .file_stem()
+        .ok_or_else(|| anyhow!("not a program path: {}", path))?
+        .to_string_lossy()
+        .to_string();
     let name = match get_display_name(file_path) {
         Ok(name) => {
             if name.is_empty() {
@@ -62,7 +101,7 @@ pub fn get_program_by_path(path: String) -> Result<Program> {
         }
         Err(_) => stem,
     };
-    let mut icon_cache = icons::ICON_CACHE.lock().unwrap();
+    let mut icon_cache = icons::ICON_CACHE.lock();
     let icon = icon_cache.get_png(file_path.to_path_buf())?.to_vec();
     Ok(Program { path, name, icon })
 }
@@ -185,17 +224,30 @@ fn get_window_pid(hwnd: HWND) -> u32 {
     pid
 }
 
-fn get_program_path_by_hwnd(hwnd: HWND) -> Option<String> {
-    let pid = get_window_pid(hwnd);
-    get_program_path(pid)
-}
-
 fn get_program_path(pid: u32) -> Option<String> {
     let handle =
         match unsafe { OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, None, pid) } {
             Ok(v) => v,
             Err(_) => return None,
         };
+    let path = get_process_start_time(handle)
+        .and_then(|start_time| PATHS.resolve(pid, start_time, || get_image_name(handle)));
+    unsafe {
+        let _ = CloseHandle(handle);
+    }
+    path
+}
+
+fn get_process_start_time(handle: HANDLE) -> Option<u64> {
+    let mut creation = FILETIME::default();
+    let mut exit = FILETIME::default();
+    let mut kernel = FILETIME::default();
+    let mut user = FILETIME::default();
+    unsafe { GetProcessTimes(handle, &mut creation, &mut exit, &mut kernel, &mut user) }.ok()?;
+    Some(((creation.dwHighDateTime as u64) << 32) | creation.dwLowDateTime as u64)
+}
+
+fn get_image_name(handle: HANDLE) -> Option<String> {
     let mut len: u32 = MAX_PATH;
     let mut name = vec![0u16; len as usize];
     let ret = unsafe {
@@ -217,18 +269,29 @@ fn get_program_path(pid: u32) -> Option<String> {
     Some(module_path)
 }
 
-pub fn get_mouse_area_program_path() -> Option<String> {
+fn get_window_info(hwnd: HWND) -> Option<WindowInfo> {
+    let root = unsafe { GetAncestor(hwnd, GA_ROOT) };
+    let pid = get_window_pid(root);
+    let path = get_program_path(pid)?;
+    Some(WindowInfo {
+        pid,
+        path,
+        title: get_window_title(root),
+    })
+}
+
+pub fn get_mouse_area_program() -> Option<WindowInfo> {
     let mut point = POINT { x: 0, y: 0 };
     let hwnd = unsafe {
         let _ = GetCursorPos(&mut point);
         WindowFromPoint(point)
     };
-    get_program_path_by_hwnd(hwnd)
+    get_window_info(hwnd)
 }
 
-pub fn get_foreground_program_path() -> Option<String> {
+pub fn get_foreground_program() -> Option<WindowInfo> {
     let hwnd = unsafe { GetForegroundWindow() };
-    get_program_path_by_hwnd(hwnd)
+    get_window_info(hwnd)
 }
 
 #[derive(Debug)]
@@ -279,16 +342,20 @@ unsafe extern "system" fn win_event_proc(
         return;
     }
 
-    let path = get_program_path_by_hwnd(hwnd);
-    if path.is_none() {
+    let window = get_window_info(hwnd);
+    if window.is_none() {
         return;
     }
-    let path = path.unwrap();
+    let window = window.unwrap();
 
     let _ = WATCHER_EVENT_CHANNEL.lock().0.send(WatcherEvent {
-        path,
+        path: window.path,
         is_audio: false,
         active: true,
+        input: false,
+        source: None,
+        title: Some(window.title),
+        pid: Some(window.pid),
     });
 }
 
@@ -316,40 +383,56 @@ impl App {
 
     pub fn start() -> anyhow::Result<()> {
         let _watcher = Watcher::init()?;
-        watch_audio();
+        EVENT_LOOP_THREAD.store(unsafe { GetCurrentThreadId() }, Ordering::Relaxed);
         Self::eventloop()
     }
+
+    /// Quits the message loop, the hook is removed once the watcher drops.
+    pub fn stop() {
+        let thread = EVENT_LOOP_THREAD.load(Ordering::Relaxed);
+        if thread != 0 {
+            let _ = unsafe { PostThreadMessageW(thread, WM_QUIT, WPARAM(0), LPARAM(0)) };
+        }
+    }
 }
 
-fn watch_audio() {
-    thread::spawn(|| {
-        let context = Win32Context::new(|event, path| match event {
-            AudioSessionEvent::StateChange(state) => {
-                let active = state == SessionState::Active;
-                let _ = WATCHER_EVENT_CHANNEL.lock().0.send(WatcherEvent {
-                    path,
-                    is_audio: true,
-                    active,
-                });
-            }
-            _ => {}
-        });
-        loop {
-            if let Ok(event) = WATCHER_STATUS_CHANNEL.lock().1.try_recv() {
-                if event.running {
-                    let list = context.read().get_active_session_filename();
-                    for path in list {
-                        let _ = WATCHER_EVENT_CHANNEL.lock().0.send(WatcherEvent {
-                            path,
-                            is_audio: true,
-                            active: true,
-                        });
-                    }
+/// Sends an event whenever a program starts or stops playing audio, until shutdown.
+pub fn watch_audio() -> anyhow::Result<()> {
+    let context = Win32Context::new(|event, path| match event {
+        AudioSessionEvent::StateChange(state) => {
+            let active = state == SessionState::Active;
+            let _ = WATCHER_EVENT_CHANNEL.lock().0.send(WatcherEvent {
+                path,
+                is_audio: true,
+                active,
+                input: false,
+                source: None,
+                title: None,
+                pid: None,
+            });
+        }
+        _ => {}
+    });
+    while !watcher::is_shutdown() {
+        if let Ok(event) = WATCHER_STATUS_CHANNEL.lock().1.try_recv() {
+            if event.running {
+                let list = context.read().get_active_session_filename();
+                for path in list {
+                    let _ = WATCHER_EVENT_CHANNEL.lock().0.send(WatcherEvent {
+                        path,
+                        is_audio: true,
+                        active: true,
+                        input: false,
+                        source: None,
+                        title: None,
+                        pid: None,
+                    });
                 }
             }
-            thread::sleep(Duration::from_millis(1000 / 60));
         }
-    });
+        thread::sleep(Duration::from_millis(1000 / 60));
+    }
+    Ok(())
 }
 
 mod tests {
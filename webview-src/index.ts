@@ -7,10 +7,179 @@ export interface Program {
   icon: Array<number>
 }
 
-interface WindowStatus {
-  path: string,
-  active: boolean,
+export interface Repository {
+  root: string
+  name: string
+  branch: string | null
+}
+
+export type Activity = {
+  kind: 'shell'
+  cwd: string
+  command: string | null
+} | {
+  kind: 'browser'
+  domain: string
+  url: string | null
+  title: string | null
+}
+
+export interface Category {
+  name: string
+  /** Productivity weight, from -1 (distracting) to 1 (productive). */
+  weight: number
+}
+
+export type StatusReason =
+  | 'focus'
+  | 'input'
+  | 'audio_start'
+  | 'audio_stop'
+  | 'timeout'
+  | 'replaced'
+  | 'suspended'
+  | 'inactive'
+  | 'locked'
+  | 'sleep'
+  | 'clock_changed'
+  | 'manual_start'
+  | 'manual_stop'
+  | 'shutdown'
+
+export interface WindowStatus {
+  /** Increased when the fields change incompatibly. */
+  version: 2
+  path: string
+  active: boolean
   time: number
+  reason: StatusReason
+  isAudio: boolean
+  pid: number | null
+  activeSince: number
+  /** Milliseconds the program was active, when it becomes inactive. */
+  duration: number | null
+  project: string | null
+  repository: Repository | null
+  activity: Activity | null
+  category: Category | null
+  /** Id of the custom source the program's events come from. */
+  source: string | null
+  /** Monotonic milliseconds when the event was emitted, unaffected by clock changes. */
+  monotonic: number
+}
+
+export interface Jump {
+  kind: 'sleep' | 'adjusted'
+  from: number
+  to: number
+  monotonic: number
+}
+
+export interface ScheduleChange {
+  cause: 'schedule' | 'timer'
+  running: boolean
+  time: number
+}
+
+export interface Toggled {
+  /** Whether tracking was resumed, it only runs inside the schedule. */
+  resumed: boolean
+  running: boolean
+  time: number
+}
+
+export type BackendStatus = {
+  name: string
+  /** When the state started. */
+  since: number
+  /** Times the thread was restarted after failing. */
+  restarts: number
+} & (
+  | { state: 'running' | 'stopped' }
+  | { state: 'failed', error: string }
+)
+
+export interface ProgramStatus {
+  path: string
+  activeSince: number
+  reason: 'focus' | 'input' | 'audio' | 'manual'
+  /** Id of the custom source the program's events come from. */
+  source: string | null
+  /** Milliseconds until the program times out without input, `null` while playing audio, for manual activities and while one runs. */
+  timeoutRemaining: number | null
+  project: string | null
+  repository: Repository | null
+  activity: Activity | null
+  category: Category | null
+}
+
+export interface WatcherState {
+  /** Whether tracking runs, it needs to be resumed and inside the schedule. */
+  running: boolean
+  resumed: boolean
+  suspendedUntil: number | null
+  scheduled: boolean
+  backends: Array<BackendStatus>
+  pool: Array<ProgramStatus>
+}
+
+/** Event payloads carry a sequence number, increasing by one per event. */
+export type Sequenced<T> = T & { seq: number }
+
+export interface EventRecord {
+  seq: number
+  /** Event name, e.g. `plugin:shion-watcher://status-changed`. */
+  event: string
+  payload: Sequenced<unknown>
+}
+
+/** What was missed since an event, a snapshot of the state once the events are gone. */
+export type Replay =
+  | { kind: 'events', events: Array<EventRecord> }
+  | { kind: 'snapshot', seq: number, state: WatcherState }
+
+export type PresenceState = 'active' | 'idle' | 'away' | 'locked'
+
+export interface PresenceStatus {
+  state: PresenceState
+  /** When the state started, for idle and away the last input. */
+  since: number
+}
+
+export type GroupBy = 'program' | 'project' | 'repository' | 'directory' | 'command' | 'domain' | 'category'
+
+export interface Aggregate {
+  key: string | null
+  duration: number
+  count: number
+  weight: number | null
+}
+
+export interface Productivity {
+  productive: number
+  distracting: number
+  neutral: number
+  score: number
+}
+
+export interface Classification {
+  project: string | null
+  category: string | null
+  timeout: number | null
+  ignored: boolean
+}
+
+export interface Evaluation {
+  source: string
+  priority: number
+  rule: string
+  matched: boolean
+  applied: Array<string>
+}
+
+export interface Explanation {
+  classification: Classification
+  rules: Array<Evaluation>
 }
 
 export function getProgramList() {
@@ -31,12 +200,98 @@ export function resume() {
   return invoke('plugin:shion-watcher|resume')
 }
 
+/** Suspends for `duration` milliseconds. */
+export function suspendFor(duration: number) {
+  return invoke('plugin:shion-watcher|suspend_for', {
+    duration
+  })
+}
+
+export function suspendUntil(time: number) {
+  return invoke('plugin:shion-watcher|suspend_until', {
+    time
+  })
+}
+
 export function isActive(path: string) {
   return invoke<boolean>('plugin:shion-watcher|is_active', {
     path
   })
 }
 
-export function onStatusChanged(fn: EventCallback<WindowStatus>) {
+export function getAggregates(groupBy: GroupBy, since?: number) {
+  return invoke<Array<Aggregate>>('plugin:shion-watcher|get_aggregates', {
+    groupBy,
+    since
+  })
+}
+
+export function getProductivity(since?: number) {
+  return invoke<Productivity>('plugin:shion-watcher|get_productivity', {
+    since
+  })
+}
+
+export function explainClassification(path: string, title?: string) {
+  return invoke<Explanation>('plugin:shion-watcher|explain_classification', {
+    path,
+    title
+  })
+}
+
+export function getPresence() {
+  return invoke<PresenceStatus>('plugin:shion-watcher|get_presence')
+}
+
+export function getStatus() {
+  return invoke<WatcherState>('plugin:shion-watcher|get_status')
+}
+
+/** Logs offline work, like a meeting, until stopped, pausing idle timeouts meanwhile. Fails while tracking is suspended. */
+export function startManualActivity(label: string) {
+  return invoke('plugin:shion-watcher|start_manual_activity', {
+    label
+  })
+}
+
+export function stopManualActivity() {
+  return invoke('plugin:shion-watcher|stop_manual_activity')
+}
+
+/** Catches up after missing events, e.g. when the webview reloaded. */
+export function getEventsSince(seq: number) {
+  return invoke<Replay>('plugin:shion-watcher|get_events_since', {
+    seq
+  })
+}
+
+export function getShellIntegration(shell: 'bash' | 'zsh' | 'fish') {
+  return invoke<string>('plugin:shion-watcher|get_shell_integration', {
+    shell
+  })
+}
+
+export function onStatusChanged(fn: EventCallback<Sequenced<WindowStatus>>) {
   return listen('plugin:shion-watcher://status-changed', fn)
 }
+
+export function onPresenceChanged(fn: EventCallback<Sequenced<PresenceStatus>>) {
+  return listen('plugin:shion-watcher://presence-changed', fn)
+}
+
+export function onClockJumped(fn: EventCallback<Sequenced<Jump>>) {
+  return listen('plugin:shion-watcher://clock-jumped', fn)
+}
+
+export function onScheduleChanged(fn: EventCallback<Sequenced<ScheduleChange>>) {
+  return listen('plugin:shion-watcher://schedule-changed', fn)
+}
+
+export function onWatcherStateChanged(fn: EventCallback<Sequenced<WatcherState>>) {
+  return listen('plugin:shion-watcher://watcher-state-changed', fn)
+}
+
+/** The hotkey suspended or resumed tracking. */
+export function onToggled(fn: EventCallback<Sequenced<Toggled>>) {
+  return listen('plugin:shion-watcher://toggled', fn)
+}